@@ -1,23 +1,29 @@
 use actix_web::HttpResponse;
-use actix_web::{get, middleware::Logger, post, put, web, App, HttpServer, Responder};
-use chrono::{DateTime, NaiveDateTime};
+use actix_web::{delete, get, middleware::Logger, post, put, web, App, HttpServer, Responder};
+use chrono::NaiveDateTime;
 use chrono::Utc;
 use env_logger;
 use log;
 use serde::{Deserialize, Serialize};
 use clap::Parser;
 use serde_json::to_string_pretty;
-use wait_timeout::ChildExt;
 use std::fs::read_to_string;
 use std::fs::File;
 use std::io::BufRead;
+use std::io::Read;
 use std::io::BufReader;
 use std::io::Write;
-use std::mem::size_of_val;
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::os::unix::process::{CommandExt, ExitStatusExt};
 use std::{clone, process};
 use std::io;
 use std::process::ExitStatus;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Condvar, Mutex};
+use std::collections::{HashMap, HashSet, VecDeque};
+use base64::Engine;
+use sha2::{Digest, Sha256};
 use rusqlite::{Connection,params};
 
 //this block defines global variables
@@ -26,12 +32,39 @@ lazy_static::lazy_static! {
     static ref JOB_LIST: Arc<Mutex<Vec<Job>>> = Arc::new(Mutex::new(Vec::new()));
     //save users
     static ref USERS_LIST: Arc<Mutex<Vec<User>>> = Arc::new(Mutex::new(Vec::new()));
+    //save problems, problems in config are saved at first start
+    static ref PROBLEMS_LIST: Arc<Mutex<Vec<Problem>>> = Arc::new(Mutex::new(Vec::new()));
     //save contests
     static ref CONTESTS_LIST: Arc<Mutex<Vec<Contest>>> = Arc::new(Mutex::new(Vec::new()));
     //save submitting amount for each contest[index] by (user_id, submission_amount)
     static ref CONTESTS_SUB_LIMIT: Arc<Mutex<Vec<Vec<(usize, usize)>>>> = Arc::new(Mutex::new(Vec::new()));
     //save start argument to make it easy to approach
-    static ref ARGL: Arc<Mutex<Argu>> = Arc::new(Mutex::new(Argu{config: "".to_string(), flush_data: false}));
+    static ref ARGL: Arc<Mutex<Argu>> = Arc::new(Mutex::new(Argu{config: "".to_string(), flush_data: false, worker: None, worker_name: None}));
+    //directory of the work directories of this process, a remote worker process has its own under tmp
+    static ref WORK_ROOT: Arc<Mutex<String>> = Arc::new(Mutex::new("tmp/server".to_string()));
+    //count test runs to give each of them a work directory
+    static ref RUN_COUNT: Arc<Mutex<usize>> = Arc::new(Mutex::new(0));
+    //save rejudges of many jobs
+    static ref REJUDGE_LIST: Arc<Mutex<Vec<Rejudge>>> = Arc::new(Mutex::new(Vec::new()));
+    //save ids of jobs waiting to be judged
+    static ref JOB_QUEUE: Arc<(Mutex<VecDeque<usize>>, Condvar)> = Arc::new((Mutex::new(VecDeque::new()), Condvar::new()));
+    //save ids of running jobs which are canceled but not stopped by their workers yet,
+    //always locked after JOB_LIST when both are needed
+    static ref CANCELED_JOBS: Arc<Mutex<HashSet<usize>>> = Arc::new(Mutex::new(HashSet::new()));
+    //send progress of jobs to event streams
+    static ref JOB_EVENTS: tokio::sync::broadcast::Sender<JobEvent> = tokio::sync::broadcast::channel(1024).0;
+    //save remote workers by id, always locked after JOB_LIST when both are needed
+    static ref WORKERS: Arc<Mutex<HashMap<usize, Worker>>> = Arc::new(Mutex::new(HashMap::new()));
+    static ref WORKER_COUNT: Arc<Mutex<usize>> = Arc::new(Mutex::new(0));
+    //data files a remote worker has downloaded by path, with their size and modified time in ms on the server
+    static ref FETCHED_DATA: Arc<Mutex<HashMap<String, (u64, u64)>>> = Arc::new(Mutex::new(HashMap::new()));
+}
+
+std::thread_local! {
+    //the job judged by this thread, so that its programs can be killed when it is canceled
+    static CURRENT_JOB: std::cell::Cell<Option<usize>> = const { std::cell::Cell::new(None) };
+    //server url, worker id and worker token when this thread judges for a remote server
+    static REMOTE_WORKER: std::cell::RefCell<Option<(String, usize, String)>> = const { std::cell::RefCell::new(None) };
 }
 
 //define struct for input and output in request below
@@ -42,12 +75,19 @@ struct Argu {
     config: String,
     #[arg(long, short = 'f')]
     flush_data: bool,
+    //run as a remote judge worker of the server at this url instead of a server
+    #[arg(long)]
+    worker: Option<String>,
+    #[arg(long)]
+    worker_name: Option<String>,
 }
 
 #[derive(Clone)]
 #[derive(Serialize, Deserialize)]
 struct Config {
     server: Server,
+    //problems can also be made by post /problems
+    #[serde(default)]
     problems: Vec<Problem>,
     languages: Vec<Language>,
 }
@@ -57,6 +97,50 @@ struct Config {
 struct Server {
     bind_address: Option<String>,
     bind_port: Option<u16>,
+    judge_workers: Option<usize>,
+    //max bytes of compiler output saved in a job, 4096 when not given
+    compile_info_limit: Option<usize>,
+    //max limits of test runs, 1 second of cpu time, 256 MiB of memory and 64 KiB of output when not given
+    run_time_limit: Option<u64>,
+    run_memory_limit: Option<u64>,
+    run_output_limit: Option<u64>,
+    //programs are built again for every job when not given
+    build_cache: Option<BuildCache>,
+    //urls told about every judged job, also used for callback urls of jobs
+    webhooks: Option<Webhooks>,
+    //seconds a remote worker keeps its job without heartbeats, 30 when not given
+    worker_lease: Option<u64>,
+    //token shared by the server and its remote workers, no worker is taken when not given
+    worker_token: Option<String>,
+    //token of administrators for changing problems and rejudging, which is refused when not given
+    admin_token: Option<String>,
+    //directory of uploaded problem files, "problems" when not given
+    problem_dir: Option<String>,
+}
+
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+struct Webhooks {
+    urls: Vec<String>,
+    //key of the hmac-sha256 signature in header X-OJ-Signature, no signature when not given
+    secret: Option<String>,
+    //times to retry a failed delivery, 3 when not given
+    retries: Option<u32>,
+    //hosts callback urls of jobs may point to, no callback url is taken when not given
+    #[serde(default)]
+    callback_hosts: Vec<String>,
+}
+
+//cache of compiled programs by source and language
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+struct BuildCache {
+    //"cache" when not given, which is kept when the server restarts unlike tmp
+    dir: Option<String>,
+    //max total bytes of programs, 1 GiB when not given
+    max_size: Option<u64>,
+    //seconds a program is kept, 1 day when not given
+    max_age: Option<u64>,
 }
 
 #[derive(Clone)]
@@ -68,6 +152,22 @@ struct Problem {
     ty: String,
     misc: Option<Misc>,
     cases: Vec<Case>,
+    //scores of cases are not used when a problem has subtasks
+    subtasks: Option<Vec<Subtask>>,
+}
+
+//a group of cases scored together
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+struct Subtask {
+    score: f64,
+    //ids of cases in this subtask, starting from 1
+    cases: Vec<usize>,
+    //"all" gives the score only when all cases are accepted, "min" and "product"
+    //aggregate the score ratios of cases, "all" when not given
+    aggregation: Option<String>,
+    //ids of earlier subtasks which must be accepted, starting from 1
+    depends: Option<Vec<usize>>,
 }
 
 #[derive(Clone)]
@@ -75,6 +175,38 @@ struct Problem {
 struct Misc{
     special_judge: Option<Vec<String>>,
     dynamic_ranking_ratio: Option<f64>,
+    //max bytes of output for cases without their own limit
+    output_limit: Option<u64>,
+    //whether contestants can see the stderr of their programs in case info
+    show_stderr: Option<bool>,
+    //built-in checker used instead of std comparison:
+    //"float", "tokens", "case_insensitive" or "unordered_lines"
+    checker: Option<String>,
+    //absolute and relative error allowed by the float checker
+    epsilon: Option<f64>,
+    relative_epsilon: Option<f64>,
+    //"testlib" for checkers reporting by exit code, legacy stdout protocol when not given
+    spj_protocol: Option<String>,
+    //interactor command of interactive problems, which talks with program by stdin and stdout
+    interactor: Option<Vec<String>>,
+    //time limit in us and sandbox of special judge and interactor, 10 seconds when not given
+    spj_time_limit: Option<u64>,
+    spj_sandbox: Option<Sandbox>,
+    //files in the work directory which program reads and writes instead of stdin and stdout
+    input_file: Option<String>,
+    output_file: Option<String>,
+}
+
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+struct PostProblem {
+    id: Option<usize>,
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+    misc: Option<Misc>,
+    cases: Vec<Case>,
+    subtasks: Option<Vec<Subtask>>,
 }
 
 #[derive(Clone)]
@@ -97,26 +229,108 @@ struct Case {
     score: f64,
     input_file: String,
     answer_file: String,
+    //cpu time limit, wall time limit is 3 times of it when not given, both in us
     time_limit: u64,
+    wall_time_limit: Option<u64>,
     memory_limit: u64,
+    output_limit: Option<u64>,
+    //name of the output submitted for this case in output only problems, "{case id}.out" when not given
+    output_name: Option<String>,
 }
 
 #[derive(Clone)]
 #[derive(Serialize, Deserialize)]
 struct Language {
     name: String,
+    //main source file, in %DIR% when the submission has several files
     file_name: String,
+    //%INPUT% is the main source file, %OUTPUT% is the program and %DIR% is the directory of sources
     command: Vec<String>,
+    sandbox: Option<Sandbox>,
+    compile_sandbox: Option<Sandbox>,
+    //compile time limit in us, 10 seconds when not given, and address space limit in bytes
+    compile_time_limit: Option<u64>,
+    compile_memory_limit: Option<u64>,
+    //limits of cases are multiplied and then added by extra time in us and extra memory in bytes
+    time_multiplier: Option<f64>,
+    memory_multiplier: Option<f64>,
+    extra_time: Option<u64>,
+    extra_memory: Option<u64>,
+}
+
+//sandbox policy for running programs of a language, nothing is limited when not given
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+struct Sandbox {
+    //new network, ipc, uts, mount and pid namespaces
+    namespaces: Option<bool>,
+    //every mount read-only except the work directory
+    readonly_root: Option<bool>,
+    //rlimits in seconds, bytes and process count
+    cpu_time: Option<u64>,
+    file_size: Option<u64>,
+    processes: Option<u64>,
+    //names or numbers of syscalls that kill the program when called
+    forbidden_syscalls: Option<Vec<String>>,
+    //user and group which programs run as when the server is run by root, 65534 (nobody) when not given
+    uid: Option<u32>,
+    gid: Option<u32>,
 }
 
 #[derive(Clone)]
 #[derive(Serialize, Deserialize)]
 struct PostJob {
+    //source code and language are not used by output only problems
+    #[serde(default)]
     source_code: String,
+    #[serde(default)]
     language: String,
     user_id: usize,
     contest_id: usize,
     problem_id: usize,
+    //outputs of output only problems by their names
+    #[serde(default)]
+    outputs: Option<HashMap<String, String>>,
+    //source files by their paths, or a base64 zip/tar archive, unpacked to the %DIR% of language command
+    #[serde(default)]
+    files: Option<HashMap<String, String>>,
+    #[serde(default)]
+    archive: Option<String>,
+    //url told about the job when it is judged, its host has to be one of callback_hosts of webhooks
+    #[serde(default)]
+    callback_url: Option<String>,
+}
+
+//a test run of a program on custom input, which is not saved
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+struct PostRun {
+    #[serde(default)]
+    source_code: String,
+    language: String,
+    #[serde(default)]
+    files: Option<HashMap<String, String>>,
+    #[serde(default)]
+    archive: Option<String>,
+    #[serde(default)]
+    stdin: String,
+    //limits lower than those of the server, in us and bytes
+    time_limit: Option<u64>,
+    memory_limit: Option<u64>,
+}
+
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+struct RunResult {
+    result: String,
+    compile_info: String,
+    stdout: String,
+    stderr: String,
+    time: u128,
+    wall_time: u128,
+    memory: u64,
+    exit_code: Option<i32>,
+    signal: Option<String>,
 }
 
 #[derive(Clone)]
@@ -126,10 +340,31 @@ struct Job {
     created_time: String,
     updated_time: String,
     submission: PostJob,
-    state: String,
+    state: JobState,
+    result: String,
+    score: f64,
+    cases: Vec<CaseResult>,
+    #[serde(default)]
+    subtasks: Option<Vec<SubtaskResult>>,
+}
+
+//Failed is for jobs stopped by system errors
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Serialize, Deserialize)]
+enum JobState {
+    Queueing,
+    Running,
+    Finished,
+    Canceled,
+    Failed,
+}
+
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+struct SubtaskResult {
+    id: usize,
     result: String,
     score: f64,
-    cases: Vec<CaseResult>
 }
 
 #[derive(Clone)]
@@ -137,9 +372,17 @@ struct Job {
 struct CaseResult {
     id: usize,
     result: String,
+    //user and system cpu time in us
     time: u128,
+    #[serde(default)]
+    wall_time: u128,
     memory: u64,
-    info: String
+    info: String,
+    //exit code of a program that exits, or the signal that terminates it
+    #[serde(default)]
+    exit_code: Option<i32>,
+    #[serde(default)]
+    signal: Option<String>,
 }
 
 #[derive(Clone)]
@@ -164,6 +407,80 @@ struct GetJob {
     result: Option<String>,
 }
 
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+struct PostWorker {
+    name: String,
+    languages: Vec<String>,
+}
+
+//a remote judge worker, which is removed when it misses heartbeats for the lease
+#[derive(Clone)]
+#[derive(Serialize)]
+struct Worker {
+    id: usize,
+    name: String,
+    languages: Vec<String>,
+    job_id: Option<usize>,
+    //seconds
+    lease: u64,
+    #[serde(skip)]
+    last_seen: std::time::Instant,
+}
+
+//an event of job progress, data is json of the state, a case result or the verdict of a job
+#[derive(Clone)]
+struct JobEvent {
+    job_id: usize,
+    contest_id: usize,
+    //"state", "case" or "verdict"
+    event: String,
+    data: String,
+    //whether the job stops being judged after this event, it ends the stream of the job only
+    last: bool,
+}
+
+//jobs in a rejudge with their results before it
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+struct Rejudge {
+    id: usize,
+    created_time: String,
+    jobs: Vec<RejudgeJob>,
+}
+
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+struct RejudgeJob {
+    job_id: usize,
+    result: String,
+    score: f64,
+}
+
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+struct RejudgeProgress {
+    id: usize,
+    created_time: String,
+    total: usize,
+    judged: usize,
+    changed: usize,
+    jobs: Vec<RejudgeDiff>,
+}
+
+#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+struct RejudgeDiff {
+    job_id: usize,
+    state: JobState,
+    //whether the result or score is changed after the job is judged again
+    changed: bool,
+    before_result: String,
+    before_score: f64,
+    after_result: String,
+    after_score: f64,
+}
+
 #[derive(Clone)]
 #[derive(Serialize, Deserialize)]
 struct PostContest {
@@ -213,12 +530,24 @@ async fn greet(name: web::Path<String>) -> impl Responder {
 //basic function part
 //check if the post /jobs contents is legal
 fn check_post_job(postjob: PostJob, config: web::Data<Config>)-> Result<(Language, Problem), MyError> {
+    if let Some(e) = check_source_files(postjob.clone()) {
+        return Err(e);
+    }
+    if let Some(url) = postjob.callback_url.clone() {
+        if !callback_allowed(&url, &config) {
+            return Err(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: format!("Invalid callback url {}",url) });
+        }
+    }
     let mut cnt = 0; //counter
     //save for language infomation used
-    let mut savelang = Language{ name: String::new(), file_name: String::new(), command: Vec::new()};
+    let mut savelang = Language{ name: String::new(), file_name: String::new(), command: Vec::new(), sandbox: None, compile_sandbox: None, compile_time_limit: None, compile_memory_limit: None,
+                                time_multiplier: None, memory_multiplier: None, extra_time: None, extra_memory: None };
     //save for problem infomation used
-    let mut savecase = Problem{cases: Vec::new(), id: 0, name: String::new(), ty: String::new(), misc: None };
-    //check if language in config
+    let savecase = match this_problem(postjob.problem_id) {
+        Some(problem) => problem,
+        None => return Err(MyError{ reason: "ERR_NOT_FOUND".to_string(), code: 3, message: format!("Problem {} Not Found",postjob.problem_id),}),
+    };
+    //check if language in config, output only problems need no language
     for i in config.languages.clone() {
         if postjob.language == i.name {
             cnt += 1;
@@ -226,17 +555,7 @@ fn check_post_job(postjob: PostJob, config: web::Data<Config>)-> Result<(Languag
             break;
         }
     }
-    if cnt == 0 { return Err(MyError{ reason: "ERR_NOT_FOUND".to_string(), code: 3, message: format!("Language {} Not Found",postjob.language).to_string(),}); }
-    cnt = 0;
-    //check if problem in config
-    for i in config.problems.clone() {
-        if postjob.problem_id == i.id {
-            cnt += 1;
-            savecase = i.clone();
-            break;
-        }
-    }
-    if cnt == 0 { return Err(MyError{ reason: "ERR_NOT_FOUND".to_string(), code: 3, message: format!("Problem {} Not Found",postjob.problem_id),}); }
+    if cnt == 0 && savecase.ty != "output_only" { return Err(MyError{ reason: "ERR_NOT_FOUND".to_string(), code: 3, message: format!("Language {} Not Found",postjob.language).to_string(),}); }
     if postjob.user_id >= USERS_LIST.lock().unwrap().len() {
         return Err(MyError{ reason: "ERR_NOT_FOUND".to_string(), code: 3, message: format!("User {} Not Found",postjob.user_id),});
     }
@@ -295,34 +614,274 @@ fn check_post_job(postjob: PostJob, config: web::Data<Config>)-> Result<(Languag
     Ok((savelang, savecase))
 }
 
-//build program for oj judge
-fn build_test(postjob: PostJob, langu: Language)-> Result<ExitStatus, MyError> {
-    let _ = std::fs::remove_dir_all("tmp");
-    let _ = std::fs::create_dir("tmp");
-    let mut _inputfile = std::fs::File::create("tmp/".to_owned()+&langu.file_name.clone());
-    match _inputfile {
-        Err(_e) => return Err(MyError{ code: 6, reason: "ERR_INTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() }),
-        Ok(mut inputfile) => {
-            let _ = inputfile.write(postjob.source_code.as_bytes());
-        },
+//check paths of source files and the archive of a submission
+fn check_source_files(postjob: PostJob)-> Option<MyError> {
+    for name in postjob.files.unwrap_or_default().keys() {
+        //files must stay in the source directory
+        let path = std::path::Path::new(name);
+        if name.is_empty() || path.is_absolute() || path.components().any(|c| !matches!(c, std::path::Component::Normal(_))) {
+            return Some(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: format!("Invalid file name {}",name) });
+        }
+    }
+    if postjob.archive.is_some() && base64::engine::general_purpose::STANDARD.decode(postjob.archive.unwrap()).is_err() {
+        return Some(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "Invalid archive".to_string() });
+    }
+    None
+}
+
+//write source files of a submission to {workdir}/src and unpack its archive there.
+//the archive is unpacked by unzip or tar under the compile sandbox of the language,
+//and files are written after it by write_source_file, which never follows links the archive made.
+fn unpack_sources(postjob: PostJob, langu: Language, workdir: &str)-> Result<String, MyError> {
+    let internal_err = MyError{ code: 6, reason: "ERR_INTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() };
+    let dir = format!("{}/src",workdir);
+    std::fs::create_dir_all(&dir).map_err(|_| internal_err.clone())?;
+    if postjob.archive.is_some() {
+        let archive = base64::engine::general_purpose::STANDARD.decode(postjob.archive.unwrap())
+            .map_err(|_| MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "Invalid archive".to_string() })?;
+        let archive_path = format!("{}/archive",workdir);
+        std::fs::write(&archive_path, &archive).map_err(|_| internal_err.clone())?;
+        let mut command = if archive.starts_with(b"PK\x03\x04") {
+            let mut command = process::Command::new("unzip");
+            command.args(["-q", "-o", &archive_path, "-d", &dir]);
+            command
+        } else {
+            let mut command = process::Command::new("tar");
+            command.args(["-xf", &archive_path, "-C", &dir]);
+            command
+        };
+        command.stdin(process::Stdio::null())
+               .stdout(process::Stdio::null())
+               .stderr(process::Stdio::null())
+               .process_group(0);
+        apply_sandbox(&mut command, langu.compile_sandbox.clone(), workdir, true)?;
+        let state = command.spawn().and_then(|child| wait_child(&child, 0, langu.compile_time_limit.unwrap_or(10_000_000), 0))
+            .map_err(|_| internal_err.clone())?;
+        let _ = std::fs::remove_file(&archive_path);
+        if state.time_out || !ExitStatus::from_raw(state.status).success() {
+            return Err(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "Invalid archive".to_string() });
+        }
+    }
+    for (name, contents) in postjob.files.unwrap_or_default() {
+        //a file clashing with the archive, like one below a link, fails like a broken archive
+        write_source_file(&dir, &name, contents.as_bytes())
+            .map_err(|_| MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: format!("Invalid file name {}",name) })?;
+    }
+    Ok(dir)
+}
+
+//write a file at a relative path below dir, making directories on the way.
+//every step is opened from the one before without following links, so nothing outside dir is written
+fn write_source_file(dir: &str, name: &str, contents: &[u8])-> Result<(), io::Error> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid file name {}",name));
+    let mut parts: Vec<std::ffi::CString> = Vec::new();
+    for component in std::path::Path::new(name).components() {
+        match component {
+            std::path::Component::Normal(part) => parts.push(std::ffi::CString::new(part.as_bytes()).map_err(|_| invalid())?),
+            _ => return Err(invalid()),
+        }
+    }
+    let file_name = parts.pop().ok_or_else(invalid)?;
+    let mut parent = std::fs::OpenOptions::new().read(true).custom_flags(libc::O_DIRECTORY | libc::O_NOFOLLOW).open(dir)?;
+    for part in parts {
+        unsafe {
+            if libc::mkdirat(parent.as_raw_fd(), part.as_ptr(), 0o755) != 0 && *libc::__errno_location() != libc::EEXIST {
+                return Err(io::Error::last_os_error());
+            }
+            let fd = libc::openat(parent.as_raw_fd(), part.as_ptr(), libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW | libc::O_CLOEXEC);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            parent = File::from_raw_fd(fd);
+        }
+    }
+    let file = unsafe {
+        //a file left by the archive is replaced, the new one is created only if nothing is there
+        libc::unlinkat(parent.as_raw_fd(), file_name.as_ptr(), 0);
+        let fd = libc::openat(parent.as_raw_fd(), file_name.as_ptr(), libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL | libc::O_NOFOLLOW | libc::O_CLOEXEC, 0o644);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        File::from_raw_fd(fd)
+    };
+    (&file).write_all(contents)
+}
+
+//key of a build in cache, which is the hash of everything used in compiling
+fn build_key(postjob: PostJob, langu: Language)-> String {
+    let mut hasher = Sha256::new();
+    //every part is led by its length, so different parts never give the same bytes
+    let mut add = |part: &[u8]| {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    };
+    add(langu.name.as_bytes());
+    add(langu.file_name.as_bytes());
+    for i in langu.command.iter() {
+        add(i.as_bytes());
+    }
+    add(postjob.source_code.as_bytes());
+    let mut files: Vec<(String, String)> = postjob.files.unwrap_or_default().into_iter().collect();
+    files.sort();
+    for (name, contents) in files {
+        add(name.as_bytes());
+        add(contents.as_bytes());
+    }
+    add(postjob.archive.unwrap_or_default().as_bytes());
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+//remove programs older than max age, then the oldest ones until the cache is not larger than max size
+fn clean_build_cache(dir: &str, max_size: u64, max_age: u64) {
+    let mut entries: Vec<(std::time::SystemTime, u64, std::path::PathBuf)> = Vec::new();
+    for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.extension().is_some() {
+            continue;
+        }
+        if let Ok(meta) = entry.metadata() {
+            entries.push((meta.modified().unwrap_or(std::time::UNIX_EPOCH), meta.len(), path));
+        }
+    }
+    entries.sort();
+    let mut total: u64 = entries.iter().map(|e| e.1).sum();
+    for (modified, size, path) in entries {
+        let age = modified.elapsed().map(|d| d.as_secs()).unwrap_or(0);
+        if age <= max_age && total <= max_size {
+            break;
+        }
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("info"));
+        total -= size;
+    }
+}
+
+//build a program or take it from the build cache, only successful builds are cached.
+//a program is saved as {key} with its compile info as {key}.info, both are written to
+//temporary files first, so other workers never see a half written program.
+fn cached_build(postjob: PostJob, langu: Language, workdir: &str, config: web::Data<Config>)-> Result<(ExitStatus, String), MyError> {
+    let info_limit = config.server.compile_info_limit.unwrap_or(4096);
+    if config.server.build_cache.is_none() {
+        return build_test(postjob, langu, workdir, info_limit);
+    }
+    let cache = config.server.build_cache.clone().unwrap();
+    let dir = cache.dir.unwrap_or("cache".to_string());
+    let max_age = cache.max_age.unwrap_or(24 * 60 * 60);
+    let key = build_key(postjob.clone(), langu.clone());
+    let program_path = format!("{}/{}",dir,key);
+    let info_path = format!("{}/{}.info",dir,key);
+    let fresh = std::fs::metadata(&program_path).and_then(|m| m.modified())
+        .map(|t| t.elapsed().map(|d| d.as_secs()).unwrap_or(0) <= max_age).unwrap_or(false);
+    if fresh {
+        let _ = std::fs::remove_dir_all(workdir);
+        if std::fs::create_dir_all(workdir).is_err() {
+            return Err(MyError{ code: 6, reason: "ERR_INTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() });
+        }
+        let info = read_to_string(&info_path);
+        //the program may be removed by another worker at the same time, then it is built again
+        if info.is_ok() && std::fs::copy(&program_path, format!("{}/out_put_program",workdir)).is_ok() {
+            return Ok((ExitStatus::from_raw(0), info.unwrap()));
+        }
+    }
+    let (status, info) = build_test(postjob, langu, workdir, info_limit)?;
+    if status.success() && std::fs::create_dir_all(&dir).is_ok() {
+        let tmp_path = format!("{}/{}.{}",dir,key,workdir.replace('/', "_"));
+        if std::fs::write(format!("{}.info",tmp_path), &info).is_ok() && std::fs::rename(format!("{}.info",tmp_path), &info_path).is_ok()
+            && open_work_file(&format!("{}/out_put_program",workdir)).and_then(|mut program| {
+                let mut cached = File::create(&tmp_path)?;
+                io::copy(&mut program, &mut cached)?;
+                cached.set_permissions(std::fs::Permissions::from_mode(0o755))
+            }).is_ok() {
+            let _ = std::fs::rename(&tmp_path, &program_path);
+        }
+        let _ = std::fs::remove_file(&tmp_path);
+        clean_build_cache(&dir, cache.max_size.unwrap_or(1 << 30), max_age);
+    }
+    Ok((status, info))
+}
+
+//build program for oj judge in its own work directory
+//compiler output is returned together with the status, cut to info_limit bytes
+fn build_test(postjob: PostJob, langu: Language, workdir: &str, info_limit: usize)-> Result<(ExitStatus, String), MyError> {
+    let _ = std::fs::remove_dir_all(workdir);
+    if std::fs::create_dir_all(workdir).is_err() {
+        return Err(MyError{ code: 6, reason: "ERR_INTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() });
+    }
+    //a submission of several files is compiled in its source directory
+    let mut dir = workdir.to_string();
+    if postjob.files.is_some() || postjob.archive.is_some() {
+        match unpack_sources(postjob.clone(), langu.clone(), workdir) {
+            Ok(src_dir) => dir = src_dir,
+            //a broken archive fails like a compilation
+            Err(e) if e.code == 1 => return Ok((ExitStatus::from_raw(1 << 8), e.message)),
+            Err(e) => return Err(e),
+        }
+    }
+    if !postjob.source_code.is_empty() || dir == workdir {
+        //the source directory may hold links from the archive
+        if write_source_file(&dir, &langu.file_name, postjob.source_code.as_bytes()).is_err() {
+            return Err(MyError{ code: 6, reason: "ERR_INTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() });
+        }
     }
     let mut comargs: Vec<String> = Vec::new();
     for i in langu.command.clone() {
         if i == "%INPUT%" {
-            comargs.push("tmp/".to_owned()+&langu.file_name.clone());
+            comargs.push(format!("{}/{}",dir,langu.file_name));
         } else if i == "%OUTPUT%" {
-            comargs.push("tmp/out_put_program".to_string());
+            comargs.push(format!("{}/out_put_program",workdir));
+        } else if i == "%DIR%" {
+            comargs.push(dir.clone());
         } else {
             comargs.push(i);
         }
     }
     comargs.remove(0);
-    let _status = process::Command::new(langu.command[0].clone())
-                    .args(comargs)
-                    .status();
+    //stdout and stderr of the compiler share one pipe, one byte more than info_limit is kept to know it is cut
+    let capture = capture_pipe(info_limit.saturating_add(1)).and_then(|(w, capture)| Ok((w.try_clone()?, w, capture)));
+    if capture.is_err() {
+        return Err(MyError{ code: 6, reason: "ERR_INTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() });
+    }
+    let (log_out, log_err, capture) = capture.unwrap();
+    let mut command = process::Command::new(langu.command[0].clone());
+    command.args(comargs)
+           .stdin(process::Stdio::null())
+           .stdout(process::Stdio::from(log_out))
+           .stderr(process::Stdio::from(log_err))
+           .process_group(0);
+    let compile_memory_limit = langu.compile_memory_limit.unwrap_or(0);
+    if compile_memory_limit > 0 {
+        unsafe { command.pre_exec(move || set_rlimit(libc::RLIMIT_AS, compile_memory_limit)); }
+    }
+    apply_sandbox(&mut command, langu.compile_sandbox.clone(), workdir, true)?;
+    let _status = command.spawn().and_then(|child| wait_child(&child, 0, langu.compile_time_limit.unwrap_or(10_000_000), 0));
+    //the pipe held by the command must be closed, or the capture never sees its end
+    drop(command);
     match _status {
         Err(_e) => return Err(MyError{ code: 6, reason: "ERR_INTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() }),
-        Ok(status) => return Ok(status),
+        Ok(state) => {
+            let mut info = String::new();
+            if state.time_out {
+                info.push_str("Compile Time Limit Exceeded\n");
+            }
+            let log = captured_bytes(capture);
+            info.push_str(&String::from_utf8_lossy(&log[..log.len().min(info_limit)]));
+            if log.len() > info_limit {
+                info.push_str("\n...");
+            }
+            return Ok((ExitStatus::from_raw(state.status), info));
+        },
+    }
+}
+
+//result of a program which does not finish normally
+fn status_result(status: i32)-> Option<String> {
+    match status {
+        1 => Some("Runtime Error".to_string()),
+        2 => Some("Time Limit Exceeded".to_string()),
+        3 => Some("Memory Limit Exceeded".to_string()),
+        4 => Some("Dangerous Syscall".to_string()),
+        5 => Some("Output Limit Exceeded".to_string()),
+        _ => None,
     }
 }
 
@@ -330,130 +889,1984 @@ fn build_test(postjob: PostJob, langu: Language)-> Result<ExitStatus, MyError> {
 struct Runstate {
     status: i32,
     runtime: u128,
+    wall_time: u128,
+    memory: u64,
+    exit_code: Option<i32>,
+    signal: Option<i32>,
+    stderr: String,
+}
+
+//max bytes of stderr kept for a case
+const STDERR_INFO_LIMIT: usize = 1024;
+
+//stderr of a program read through a pipe, only the first bytes are kept and the rest is dropped,
+//so that a program writing stderr endlessly takes no disk. compilers write their output in the same way
+struct StderrCapture {
+    kept: Arc<Mutex<Vec<u8>>>,
+    reader: std::thread::JoinHandle<()>,
+}
+
+fn capture_stderr(command: &mut process::Command)-> Result<StderrCapture, io::Error> {
+    let (writer, capture) = capture_pipe(STDERR_INFO_LIMIT)?;
+    command.stderr(writer);
+    Ok(capture)
+}
+
+//start reading a pipe and keep at most limit bytes, the writer is given to the program
+fn capture_pipe(limit: usize)-> Result<(io::PipeWriter, StderrCapture), io::Error> {
+    let (mut pipe, writer) = io::pipe()?;
+    let kept = Arc::new(Mutex::new(Vec::new()));
+    let buffer = kept.clone();
+    let reader = std::thread::spawn(move || {
+        let mut chunk = [0u8; 4096];
+        while let Ok(n) = pipe.read(&mut chunk) {
+            if n == 0 {
+                break;
+            }
+            let mut buffer = buffer.lock().unwrap();
+            let room = limit - buffer.len();
+            buffer.extend_from_slice(&chunk[..n.min(room)]);
+        }
+    });
+    Ok((writer, StderrCapture{ kept: kept, reader: reader }))
+}
+
+//stderr of an ended program, processes it left may still hold the pipe so the reader is not waited for long
+fn captured_stderr(capture: StderrCapture)-> String {
+    String::from_utf8_lossy(&captured_bytes(capture)).to_string()
+}
+
+fn captured_bytes(capture: StderrCapture)-> Vec<u8> {
+    let start = std::time::Instant::now();
+    while !capture.reader.is_finished() && start.elapsed() < std::time::Duration::from_millis(100) {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    let kept = capture.kept.lock().unwrap().clone();
+    kept
+}
+
+//get the name of a signal that terminates a program
+fn signal_name(sig: i32)-> String {
+    match sig {
+        libc::SIGSEGV => "SIGSEGV".to_string(),
+        libc::SIGFPE => "SIGFPE".to_string(),
+        libc::SIGABRT => "SIGABRT".to_string(),
+        libc::SIGBUS => "SIGBUS".to_string(),
+        libc::SIGILL => "SIGILL".to_string(),
+        libc::SIGKILL => "SIGKILL".to_string(),
+        libc::SIGTERM => "SIGTERM".to_string(),
+        libc::SIGPIPE => "SIGPIPE".to_string(),
+        libc::SIGSYS => "SIGSYS".to_string(),
+        libc::SIGXCPU => "SIGXCPU".to_string(),
+        libc::SIGXFSZ => "SIGXFSZ".to_string(),
+        _ => format!("SIG{}", sig),
+    }
+}
+
+//process control part
+//programs are waited by wait4 instead of std, so that the rusage of the finished
+//process can be read. peak memory comes from ru_maxrss and cpu time from ru_utime and ru_stime.
+//the peak memory and cpu time are also checked while the program is running
+//to kill it once it uses too much. a program is forked from the server before exec,
+//so its memory is only read after exec and ru_maxrss is checked against the peak before exec.
+
+//save a finished process temporarily
+struct Waitstate {
+    status: libc::c_int,
+    usage: libc::rusage,
+    time_out: bool,
+    memory_out: bool,
+    //peak memory seen after exec in bytes
     memory: u64,
+    cpu_time: std::time::Duration,
+    runtime: std::time::Duration,
+}
+
+//set a resource limit in the child process before exec
+fn set_rlimit(resource: libc::__rlimit_resource_t, value: u64)-> Result<(), io::Error> {
+    //a limit set before (by the case or the sandbox) is only made tighter, raising it is not allowed
+    let mut lim = libc::rlimit{ rlim_cur: 0, rlim_max: 0 };
+    if unsafe { libc::getrlimit(resource, &mut lim) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let value = (value as libc::rlim_t).min(lim.rlim_max);
+    let lim = libc::rlimit{ rlim_cur: value, rlim_max: value };
+    if unsafe { libc::setrlimit(resource, &lim) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+//read peak resident memory of a running process in bytes, once it has executed another program than the server
+fn program_memory(pid: libc::pid_t)-> Option<u64> {
+    if std::fs::read_link(format!("/proc/{}/exe", pid)).ok()? == std::fs::read_link("/proc/self/exe").ok()? {
+        return None;
+    }
+    let status = read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let kb: u64 = status.lines().find(|l| l.starts_with("VmHWM:"))?.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+//send the peak memory before exec back to the server, it is the memory of the server copied by fork
+//and counted in ru_maxrss of the program, which is only taken when it is clearly higher.
+//the closure must be added last so that it runs in the process which executes the program
+fn report_exec_memory(command: &mut process::Command)-> Result<io::PipeReader, io::Error> {
+    let (reader, writer) = io::pipe()?;
+    unsafe {
+        command.pre_exec(move || {
+            let mut usage: libc::rusage = std::mem::zeroed();
+            if libc::getrusage(libc::RUSAGE_SELF, &mut usage) == 0 {
+                libc::write(writer.as_raw_fd(), &usage.ru_maxrss as *const libc::c_long as *const libc::c_void, std::mem::size_of::<libc::c_long>());
+            }
+            Ok(())
+        });
+    }
+    Ok(reader)
+}
+
+//peak memory of a program in bytes from its rusage and the peak before exec read from report_exec_memory
+fn exec_memory(state: &Waitstate, mut before_exec: io::PipeReader)-> u64 {
+    let mut buf = [0u8; std::mem::size_of::<libc::c_long>()];
+    let maxrss = state.usage.ru_maxrss as u64 * 1024;
+    //code of the server run on the way to exec adds some pages after the peak is read
+    match before_exec.read_exact(&mut buf) {
+        Ok(_) if maxrss > libc::c_long::from_ne_bytes(buf) as u64 * 1024 + (1 << 20) => maxrss.max(state.memory),
+        _ => state.memory,
+    }
+}
+
+//read user and system cpu time of a running process
+fn running_cpu_time(pid: libc::pid_t)-> Option<std::time::Duration> {
+    let stat = read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    //fields after the command name, which may contain spaces, start from state
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 2..].split_whitespace().collect();
+    let ticks: u64 = fields.get(11)?.parse::<u64>().ok()? + fields.get(12)?.parse::<u64>().ok()?;
+    let hz = unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as u64;
+    Some(std::time::Duration::from_micros(ticks * 1_000_000 / hz))
+}
+
+//the program started in a new pid namespace is found under the processes named SANDBOX_NAME
+fn measured_pid(pid: libc::pid_t)-> libc::pid_t {
+    let mut pid = pid;
+    while read_to_string(format!("/proc/{}/comm", pid)).map(|c| c.trim_end() == SANDBOX_NAME.to_str().unwrap()).unwrap_or(false) {
+        let children = read_to_string(format!("/proc/{}/task/{}/children", pid, pid)).unwrap_or_default();
+        match children.split_whitespace().next().and_then(|c| c.parse().ok()) {
+            Some(child) => pid = child,
+            None => break,
+        }
+    }
+    pid
+}
+
+fn timeval_to_duration(tv: libc::timeval)-> std::time::Duration {
+    std::time::Duration::from_secs(tv.tv_sec as u64) + std::time::Duration::from_micros(tv.tv_usec as u64)
 }
 
-//run program and return result
-async fn run_test(caseinfo: Case, caseid: usize)-> Result<Runstate, io::Error> {
-    let in_file = File::open(caseinfo.input_file)?;
-    let out_file = File::create(format!("tmp/{}.out",caseid))?;
-    let mut child = process::Command::new(format!("tmp/out_put_program"))
-                     .stdin(process::Stdio::from(in_file))
-                     .stdout(process::Stdio::from(out_file))
-                     .stderr(process::Stdio::null())
-                     .spawn()?;
-    let tili = std::time::Duration::from_micros(caseinfo.time_limit);
+//wait for a child and kill it if it runs out of cpu time, wall time or memory, 0 means no limit
+//the child should lead its own process group so that processes it starts are killed with it
+fn wait_child(child: &process::Child, cpu_limit: u64, wall_limit: u64, memory_limit: u64)-> Result<Waitstate, io::Error> {
+    let pid = child.id() as libc::pid_t;
+    let cpu_tili = std::time::Duration::from_micros(cpu_limit);
+    let wall_tili = std::time::Duration::from_micros(wall_limit);
     let runt_before = std::time::Instant::now();
-    //check child if run time is out of limit
-    match child.wait_timeout(tili).unwrap() {
-        Some(status) => if status.success() {
-            let runt = runt_before.elapsed();
-            return Ok(Runstate{ status: 0, runtime: runt.as_micros(), memory: size_of_val(&child) as u64});
-        } else {
-            let runt = runt_before.elapsed();
-            return Ok(Runstate{ status: 1, runtime: runt.as_micros(), memory: size_of_val(&child) as u64});
+    let mut state = Waitstate{ status: 0, usage: unsafe { std::mem::zeroed() }, time_out: false, memory_out: false, memory: 0,
+                               cpu_time: std::time::Duration::ZERO, runtime: std::time::Duration::ZERO };
+    let mut killed = false;
+    let mut canceled = false;
+    //usage seen while running, which is all there is for a killed program in a new pid namespace
+    let mut seen_cpu = std::time::Duration::ZERO;
+    loop {
+        let r = unsafe { libc::wait4(pid, &mut state.status, libc::WNOHANG, &mut state.usage) };
+        if r == pid {
+            if canceled {
+                return Err(io::Error::new(io::ErrorKind::Interrupted, "job canceled"));
+            }
+            state.runtime = runt_before.elapsed();
+            state.cpu_time = (timeval_to_duration(state.usage.ru_utime) + timeval_to_duration(state.usage.ru_stime)).max(seen_cpu);
+            if cpu_limit > 0 && state.cpu_time > cpu_tili {
+                state.time_out = true;
+            }
+            return Ok(state);
+        } else if r < 0 {
+            return Err(io::Error::last_os_error());
         }
-        None => {
-                // child hasn't exited yet
-                child.kill().unwrap();
-                child.wait().unwrap();
-                return Ok(Runstate{ status: 2, runtime: caseinfo.time_limit as u128, memory: size_of_val(&child) as u64});
-                }
-        };
+        if !killed {
+            let running = measured_pid(pid);
+            seen_cpu = seen_cpu.max(running_cpu_time(running).unwrap_or(std::time::Duration::ZERO));
+            state.memory = state.memory.max(program_memory(running).unwrap_or(0));
+            if wall_limit > 0 && runt_before.elapsed() > wall_tili {
+                state.time_out = true;
+            } else if cpu_limit > 0 && seen_cpu > cpu_tili {
+                state.time_out = true;
+            } else if memory_limit > 0 && state.memory > memory_limit {
+                state.memory_out = true;
+            } else if current_job_canceled() {
+                canceled = true;
+            }
+            if state.time_out || state.memory_out || canceled {
+                unsafe { libc::kill(-pid, libc::SIGKILL); libc::kill(pid, libc::SIGKILL); }
+                killed = true;
+            }
+        }
+        //most programs end in a few milliseconds, which are sampled more often to see their memory
+        std::thread::sleep(std::time::Duration::from_micros(if runt_before.elapsed() < std::time::Duration::from_millis(20) { 50 } else { 500 }));
+    }
 }
+//process control part end
 
-//the following 3 parts make compare between output and answer in std/strict/spj mode
-fn cmp_output_std(caseinfo: Case, caseid: usize)-> Result<bool,io::Error> {
-    let out_put_file = File::open(format!("tmp/{}.out",caseid))?;
-    let ans_file = File::open(caseinfo.answer_file)?;
-    let output_buffer_reader = BufReader::new(out_put_file);
-    let ans_buffer_reader = BufReader::new(ans_file);
-    let mut out_put: Vec<String> = Vec::new();
-    let mut ans: Vec<String> = Vec::new();
-    //analyse file contents by lines and delete the space code on the end
-    for line in output_buffer_reader.lines() {
-        let mut read_line = line?.clone();
-            while let pop_i = read_line.pop() {
-                if pop_i.is_none(){
-                    break;
-                } else if pop_i.unwrap() != ' ' {
-                    read_line.push(pop_i.unwrap());
-                    break;
+//sandbox part
+//compilers and submitted programs run with the sandbox policy of their language.
+//everything is prepared before fork, and the closure given to pre_exec only makes
+//system calls, because allocating in the forked child of a multi-threaded server is not safe.
+//the policy is applied in order: namespaces, hidden directories and read-only root, pid namespace,
+//resource limits, dropping privileges and seccomp filter.
+//programs always run without capabilities, as the user of the policy when the server is run by root,
+//or as the user of the server in a user namespace otherwise.
+//with a mount namespace, the directory of work directories is hidden behind an empty tmpfs
+//except the work directory itself, and so are the data directories for compilers and submitted programs.
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xC000_003E;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xC000_00B7;
+//name of the processes between the server and a program in a new pid namespace
+const SANDBOX_NAME: &std::ffi::CStr = c"oj-sandbox";
+
+//find the number of a syscall by name, numbers are also accepted
+fn syscall_number(name: &str)-> Option<libc::c_long> {
+    if let Ok(n) = name.parse::<libc::c_long>() {
+        return Some(n);
+    }
+    let n = match name {
+        "socket" => libc::SYS_socket,
+        "socketpair" => libc::SYS_socketpair,
+        "connect" => libc::SYS_connect,
+        "bind" => libc::SYS_bind,
+        "listen" => libc::SYS_listen,
+        "accept" => libc::SYS_accept,
+        "accept4" => libc::SYS_accept4,
+        "sendto" => libc::SYS_sendto,
+        "recvfrom" => libc::SYS_recvfrom,
+        "clone" => libc::SYS_clone,
+        "clone3" => libc::SYS_clone3,
+        #[cfg(target_arch = "x86_64")]
+        "fork" => libc::SYS_fork,
+        #[cfg(target_arch = "x86_64")]
+        "vfork" => libc::SYS_vfork,
+        "kill" => libc::SYS_kill,
+        "tkill" => libc::SYS_tkill,
+        "ptrace" => libc::SYS_ptrace,
+        "mount" => libc::SYS_mount,
+        "umount2" => libc::SYS_umount2,
+        "pivot_root" => libc::SYS_pivot_root,
+        "chroot" => libc::SYS_chroot,
+        "unshare" => libc::SYS_unshare,
+        "setns" => libc::SYS_setns,
+        "setuid" => libc::SYS_setuid,
+        "setgid" => libc::SYS_setgid,
+        "personality" => libc::SYS_personality,
+        "reboot" => libc::SYS_reboot,
+        "kexec_load" => libc::SYS_kexec_load,
+        "init_module" => libc::SYS_init_module,
+        "delete_module" => libc::SYS_delete_module,
+        "swapon" => libc::SYS_swapon,
+        "swapoff" => libc::SYS_swapoff,
+        _ => return None,
+    };
+    Some(n)
+}
+
+//build a seccomp filter that kills the process when it calls a forbidden syscall
+fn seccomp_filter(names: Vec<String>)-> Result<Vec<libc::sock_filter>, MyError> {
+    let stmt = |code: u32, k: u32| libc::sock_filter{ code: code as u16, jt: 0, jf: 0, k: k };
+    let jump = |code: u32, k: u32, jt: u8, jf: u8| libc::sock_filter{ code: code as u16, jt: jt, jf: jf, k: k };
+    let mut filter: Vec<libc::sock_filter> = Vec::new();
+    //offsetof(struct seccomp_data, arch) is 4 and offsetof(struct seccomp_data, nr) is 0
+    filter.push(stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, 4));
+    filter.push(jump(libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K, AUDIT_ARCH, 1, 0));
+    filter.push(stmt(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_KILL_PROCESS));
+    filter.push(stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, 0));
+    //x32 syscalls have the same numbers with bit 30 set, which would pass the comparisons below
+    #[cfg(target_arch = "x86_64")]
+    {
+        filter.push(jump(libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K, 0x4000_0000, 0, 1));
+        filter.push(stmt(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_KILL_PROCESS));
+    }
+    for i in names {
+        let nr = syscall_number(&i)
+            .ok_or(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: format!("Unknown syscall {}", i) })?;
+        filter.push(jump(libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K, nr as u32, 0, 1));
+        filter.push(stmt(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_KILL_PROCESS));
+    }
+    filter.push(stmt(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_ALLOW));
+    Ok(filter)
+}
+
+//write a whole file in the child process without allocating
+fn write_proc_file(path: &std::ffi::CStr, contents: &[u8])-> Result<(), io::Error> {
+    unsafe {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let n = libc::write(fd, contents.as_ptr() as *const libc::c_void, contents.len());
+        libc::close(fd);
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[repr(C)]
+struct MountAttr {
+    attr_set: u64,
+    attr_clr: u64,
+    propagation: u64,
+    userns_fd: u64,
+}
+
+const MOUNT_ATTR_RDONLY: u64 = 0x1;
+
+//make a mount read-only or writable again, with AT_RECURSIVE also every mount below it,
+//other flags of the mounts are kept as they are
+fn set_mount_readonly(path: &std::ffi::CStr, flags: libc::c_int, readonly: bool)-> Result<(), io::Error> {
+    let (set, clr) = if readonly { (MOUNT_ATTR_RDONLY, 0) } else { (0, MOUNT_ATTR_RDONLY) };
+    let attr = MountAttr{ attr_set: set, attr_clr: clr, propagation: 0, userns_fd: 0 };
+    if unsafe { libc::syscall(libc::SYS_mount_setattr, libc::AT_FDCWD, path.as_ptr(), flags as libc::c_uint, &attr as *const MountAttr, std::mem::size_of::<MountAttr>()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+//directories of input and answer files of all problems
+fn data_dirs()-> Vec<String> {
+    let mut dirs: Vec<String> = Vec::new();
+    for problem in PROBLEMS_LIST.lock().unwrap().iter() {
+        for case in problem.cases.iter() {
+            for file in [&case.input_file, &case.answer_file] {
+                let parent = std::path::Path::new(file).parent().map(|p| p.to_string_lossy().to_string()).unwrap_or_default();
+                let parent = if parent.is_empty() { ".".to_string() } else { parent };
+                if !dirs.contains(&parent) {
+                    dirs.push(parent);
                 }
             }
-        out_put.push(read_line);
+        }
     }
-    for line in ans_buffer_reader.lines() {
-        let mut read_line = line?.clone();
-            while let pop_i = read_line.pop() {
-                if pop_i.is_none(){
-                    break;
-                } else if pop_i.unwrap() != ' ' {
-                    read_line.push(pop_i.unwrap());
-                    break;
+    dirs
+}
+
+//directories to make in a hidden directory so that the path still leads to somewhere
+fn dirs_below(path: &std::path::Path, hidden: &[std::path::PathBuf])-> Vec<std::ffi::CString> {
+    let mut dirs: Vec<std::ffi::CString> = Vec::new();
+    for h in hidden {
+        if let Ok(rest) = path.strip_prefix(h) {
+            let mut dir = h.clone();
+            for component in rest.components() {
+                dir.push(component);
+                if let Ok(c) = std::ffi::CString::new(dir.to_string_lossy().as_bytes()) {
+                    dirs.push(c);
                 }
+            }
+        }
+    }
+    dirs
+}
+
+//create a file of the server in a work directory, where a program may have left a link to anywhere,
+//so the old file is removed and the new one is created without following links
+fn create_work_file(path: &str)-> Result<File, io::Error> {
+    if let Err(e) = std::fs::remove_file(path) {
+        if e.kind() != io::ErrorKind::NotFound {
+            return Err(e);
+        }
+    }
+    std::fs::OpenOptions::new().write(true).create_new(true).custom_flags(libc::O_NOFOLLOW).open(path)
+}
+
+//open a file left by a program in a work directory, links and files which are not regular are refused
+fn open_work_file(path: &str)-> Result<File, io::Error> {
+    //a fifo would block the server without O_NONBLOCK
+    let file = std::fs::OpenOptions::new().read(true).custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK).open(path)?;
+    if !file.metadata()?.is_file() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} is not a regular file", path)));
+    }
+    Ok(file)
+}
+
+fn read_work_file(path: &str)-> Result<Vec<u8>, io::Error> {
+    let mut contents: Vec<u8> = Vec::new();
+    open_work_file(path)?.read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+//give a directory and everything in it to the user running programs
+fn chown_tree(path: &std::path::Path, uid: u32, gid: u32)-> Result<(), io::Error> {
+    std::os::unix::fs::lchown(path, Some(uid), Some(gid))?;
+    if path.is_dir() && !path.is_symlink() {
+        for entry in std::fs::read_dir(path)? {
+            chown_tree(&entry?.path(), uid, gid)?;
+        }
+    }
+    Ok(())
+}
+
+#[repr(C)]
+struct CapHeader {
+    version: u32,
+    pid: libc::c_int,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct CapData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+//drop all capabilities in the child process, also from the bounding and ambient sets
+//so that they can not come back by executing a program
+fn drop_capabilities()-> Result<(), io::Error> {
+    unsafe {
+        //errors mean there are no such capabilities to drop
+        for cap in 0..64 {
+            libc::prctl(libc::PR_CAPBSET_DROP, cap, 0, 0, 0);
+        }
+        libc::prctl(libc::PR_CAP_AMBIENT, libc::PR_CAP_AMBIENT_CLEAR_ALL, 0, 0, 0);
+        let mut header = CapHeader{ version: 0x2008_0522, pid: 0 };
+        let data = [CapData{ effective: 0, permitted: 0, inheritable: 0 }; 2];
+        if libc::syscall(libc::SYS_capset, &mut header as *mut CapHeader, data.as_ptr()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+//close every file descriptor except keep in the child process
+fn close_fds_except(keep: libc::c_int) {
+    unsafe {
+        if keep > 0 {
+            libc::syscall(libc::SYS_close_range, 0, keep - 1, 0);
+        }
+        libc::syscall(libc::SYS_close_range, keep + 1, libc::c_uint::MAX, 0);
+    }
+}
+
+//end the child process in the same way as a waited process ended
+fn exit_like(status: libc::c_int)-> ! {
+    unsafe {
+        if libc::WIFSIGNALED(status) {
+            let sig = libc::WTERMSIG(status);
+            let no_core = libc::rlimit{ rlim_cur: 0, rlim_max: 0 };
+            libc::setrlimit(libc::RLIMIT_CORE, &no_core);
+            libc::signal(sig, libc::SIG_DFL);
+            let mut set: libc::sigset_t = std::mem::zeroed();
+            libc::sigemptyset(&mut set);
+            libc::sigaddset(&mut set, sig);
+            libc::sigprocmask(libc::SIG_UNBLOCK, &set, std::ptr::null_mut());
+            libc::kill(libc::getpid(), sig);
+            libc::_exit(128 + sig);
+        }
+        libc::_exit(libc::WEXITSTATUS(status));
+    }
+}
+
+//wait for a process in the child process, 0 is returned as status when waiting fails
+fn wait_pid(pid: libc::pid_t)-> libc::c_int {
+    let mut status: libc::c_int = 0;
+    unsafe {
+        while libc::waitpid(pid, &mut status, 0) < 0 {
+            if *libc::__errno_location() != libc::EINTR {
+                return 0;
+            }
+        }
+    }
+    status
+}
+
+//the first process in a new pid namespace is its init, which ignores signals it does not handle,
+//so the program is started by a second fork and the status is sent back through a pipe.
+//both processes in between are named SANDBOX_NAME so that wait_child measures the program.
+//it returns only in the process which goes on to execute the program.
+fn fork_into_pid_namespace()-> Result<(), io::Error> {
+    let mut fds: [libc::c_int; 2] = [-1, -1];
+    unsafe {
+        if libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let init = libc::fork();
+        if init < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if init > 0 {
+            //the process waited by the server
+            libc::prctl(libc::PR_SET_NAME, SANDBOX_NAME.as_ptr());
+            close_fds_except(fds[0]);
+            let init_status = wait_pid(init);
+            let mut status: libc::c_int = 0;
+            let n = libc::read(fds[0], &mut status as *mut libc::c_int as *mut libc::c_void, std::mem::size_of::<libc::c_int>());
+            exit_like(if n == std::mem::size_of::<libc::c_int>() as isize { status } else { init_status });
+        }
+        libc::prctl(libc::PR_SET_NAME, SANDBOX_NAME.as_ptr());
+        libc::close(fds[0]);
+        let program = libc::fork();
+        if program < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if program > 0 {
+            //the init of the namespace, every process in it is killed when it exits
+            close_fds_except(fds[1]);
+            let status = wait_pid(program);
+            libc::write(fds[1], &status as *const libc::c_int as *const libc::c_void, std::mem::size_of::<libc::c_int>());
+            libc::_exit(0);
+        }
+        libc::close(fds[1]);
+    }
+    Ok(())
+}
+
+//apply a sandbox policy to a command, the work directory stays writable under a read-only root,
+//data directories are hidden when hide_data is set, which is not for special judges and interactors
+fn apply_sandbox(command: &mut process::Command, policy: Option<Sandbox>, workdir: &str, hide_data: bool)-> Result<(), MyError> {
+    if policy.is_none() {
+        return Ok(());
+    }
+    let policy = policy.unwrap();
+    let internal_err = MyError{ code: 6, reason: "ERR_INTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() };
+    let readonly_root = policy.readonly_root.unwrap_or(false);
+    let mut ns_flags: libc::c_int = 0;
+    if policy.namespaces.unwrap_or(false) {
+        ns_flags |= libc::CLONE_NEWNET | libc::CLONE_NEWIPC | libc::CLONE_NEWUTS | libc::CLONE_NEWNS | libc::CLONE_NEWPID;
+    }
+    if readonly_root {
+        ns_flags |= libc::CLONE_NEWNS;
+    }
+    let mount_ns = ns_flags & libc::CLONE_NEWNS != 0;
+    let new_pid = ns_flags & libc::CLONE_NEWPID != 0;
+    //a user namespace is needed for the others when the server is not run by root,
+    //where the program keeps the user of the server and loses the capabilities when executed
+    let uid = unsafe { libc::geteuid() };
+    let gid = unsafe { libc::getegid() };
+    let map_user = ns_flags != 0 && uid != 0;
+    if map_user {
+        ns_flags |= libc::CLONE_NEWUSER;
+    }
+    let uid_map = format!("{} {} 1", uid, uid).into_bytes();
+    let gid_map = format!("{} {} 1", gid, gid).into_bytes();
+    //a server run by root runs programs as another user, which owns the work directory
+    let change_user = uid == 0;
+    let (run_uid, run_gid) = (policy.uid.unwrap_or(65534), policy.gid.unwrap_or(65534));
+    let workdir_path = std::fs::canonicalize(workdir).map_err(|_| internal_err.clone())?;
+    if change_user {
+        chown_tree(&workdir_path, run_uid, run_gid).map_err(|_| internal_err.clone())?;
+    }
+    let workdir_c = std::ffi::CString::new(workdir_path.to_string_lossy().as_bytes()).map_err(|_| internal_err.clone())?;
+    let mut hidden: Vec<std::path::PathBuf> = Vec::new();
+    if mount_ns {
+        //work directories of the server and of worker processes on the same node are all hidden
+        let work_root = std::fs::canonicalize(&*WORK_ROOT.lock().unwrap()).map_err(|_| internal_err.clone())?;
+        hidden.extend(work_root.parent().map(|p| p.to_path_buf()));
+        if hide_data {
+            hidden.extend(data_dirs().iter().filter_map(|d| std::fs::canonicalize(d).ok()));
+        }
+        hidden.retain(|h| h != std::path::Path::new("/") && !h.starts_with(&workdir_path));
+        let all = hidden.clone();
+        hidden.retain(|h| !all.iter().any(|o| o != h && h.starts_with(o)));
+        hidden.sort();
+        hidden.dedup();
+    }
+    let cwd_path = std::env::current_dir().map_err(|_| internal_err.clone())?;
+    let mut make_dirs = dirs_below(&cwd_path, &hidden);
+    make_dirs.extend(dirs_below(&workdir_path, &hidden));
+    let hidden: Vec<std::ffi::CString> = hidden.iter().filter_map(|h| std::ffi::CString::new(h.to_string_lossy().as_bytes()).ok()).collect();
+    let mut rlimits: Vec<(libc::__rlimit_resource_t, u64)> = Vec::new();
+    if policy.cpu_time.is_some() { rlimits.push((libc::RLIMIT_CPU, policy.cpu_time.unwrap())); }
+    if policy.file_size.is_some() { rlimits.push((libc::RLIMIT_FSIZE, policy.file_size.unwrap())); }
+    if policy.processes.is_some() { rlimits.push((libc::RLIMIT_NPROC, policy.processes.unwrap())); }
+    let mut filter: Vec<libc::sock_filter> = Vec::new();
+    if policy.forbidden_syscalls.is_some() {
+        filter = seccomp_filter(policy.forbidden_syscalls.clone().unwrap())?;
+    }
+    if mount_ns {
+        //compilers write temporary files, which only can be done in the work directory
+        command.env("TMPDIR", &workdir_path);
+    }
+    unsafe {
+        command.pre_exec(move || {
+            if ns_flags != 0 && libc::unshare(ns_flags) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if map_user {
+                write_proc_file(c"/proc/self/setgroups", b"deny")?;
+                write_proc_file(c"/proc/self/uid_map", &uid_map)?;
+                write_proc_file(c"/proc/self/gid_map", &gid_map)?;
+            }
+            if mount_ns {
+                //the current directory is entered before mounting, so it is entered again to be on the new mounts
+                let mut cwd = [0 as libc::c_char; libc::PATH_MAX as usize];
+                if libc::getcwd(cwd.as_mut_ptr(), cwd.len()).is_null() {
+                    return Err(io::Error::last_os_error());
+                }
+                //the work directory is held as the current directory and bound again over the empty tmpfs
+                if libc::mount(std::ptr::null(), c"/".as_ptr(), std::ptr::null(), libc::MS_REC | libc::MS_PRIVATE, std::ptr::null()) != 0
+                    || libc::chdir(workdir_c.as_ptr()) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                for h in hidden.iter() {
+                    if libc::mount(c"tmpfs".as_ptr(), h.as_ptr(), c"tmpfs".as_ptr(), libc::MS_NOSUID | libc::MS_NODEV, c"mode=755,size=64k".as_ptr() as *const libc::c_void) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                for d in make_dirs.iter() {
+                    if libc::mkdir(d.as_ptr(), 0o755) != 0 && *libc::__errno_location() != libc::EEXIST {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                if libc::mount(c".".as_ptr(), workdir_c.as_ptr(), std::ptr::null(), libc::MS_BIND | libc::MS_REC, std::ptr::null()) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                for h in hidden.iter() {
+                    if libc::mount(std::ptr::null(), h.as_ptr(), std::ptr::null(), libc::MS_REMOUNT | libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NODEV, std::ptr::null()) != 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                //every mount is made read-only, also separate ones like /tmp and /dev/shm, except the work directory
+                if readonly_root {
+                    set_mount_readonly(c"/", libc::AT_RECURSIVE, true)?;
+                    set_mount_readonly(&workdir_c, 0, false)?;
+                }
+                if libc::chdir(cwd.as_ptr()) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            if new_pid {
+                fork_into_pid_namespace()?;
+            }
+            for (resource, value) in rlimits.iter() {
+                set_rlimit(*resource, *value)?;
+            }
+            if change_user && (libc::setgroups(0, std::ptr::null()) != 0 || libc::setresgid(run_gid, run_gid, run_gid) != 0
+                               || libc::setresuid(run_uid, run_uid, run_uid) != 0) {
+                return Err(io::Error::last_os_error());
+            }
+            drop_capabilities()?;
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if !filter.is_empty() {
+                let prog = libc::sock_fprog{ len: filter.len() as libc::c_ushort, filter: filter.as_ptr() as *mut libc::sock_filter };
+                if libc::prctl(libc::PR_SET_SECCOMP, libc::SECCOMP_MODE_FILTER, &prog as *const libc::sock_fprog) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+    Ok(())
+}
+//sandbox part end
+
+//make the command to run program in the sandbox of its language,
+//stdin and stdout are left to the caller and stderr is captured.
+//the command must be dropped after spawning, or the capture never sees the end of stderr
+fn test_command(caseinfo: Case, workdir: &str, sandbox: Option<Sandbox>, output_limit: u64)-> Result<(process::Command, StderrCapture, io::PipeReader), io::Error> {
+    //the path is absolute so that the program is found when run in the work directory
+    let mut command = process::Command::new(std::fs::canonicalize(format!("{}/out_put_program",workdir))?);
+    command.process_group(0);
+    //limit address space so that a runaway program fails to allocate,
+    //the limit is doubled because virtual memory is always larger than resident memory
+    let memory_limit = caseinfo.memory_limit;
+    if memory_limit > 0 {
+        unsafe { command.pre_exec(move || set_rlimit(libc::RLIMIT_AS, memory_limit.saturating_mul(2))); }
+    }
+    //output goes to a regular file, so writing beyond the file size limit kills the program by SIGXFSZ
+    if output_limit > 0 {
+        unsafe { command.pre_exec(move || set_rlimit(libc::RLIMIT_FSIZE, output_limit)); }
+    }
+    //the cpu rlimit is only a backstop, the limit is checked more exactly in wait_child
+    let time_limit = caseinfo.time_limit;
+    if time_limit > 0 {
+        unsafe { command.pre_exec(move || set_rlimit(libc::RLIMIT_CPU, time_limit / 1_000_000 + 1)); }
+    }
+    if let Err(e) = apply_sandbox(&mut command, sandbox, workdir, true) {
+        return Err(io::Error::new(io::ErrorKind::Other, e.message));
+    }
+    let capture = capture_stderr(&mut command)?;
+    let before_exec = report_exec_memory(&mut command)?;
+    Ok((command, capture, before_exec))
+}
+
+//wait for program started by test_command and return result
+fn wait_test(child: &process::Child, capture: StderrCapture, before_exec: io::PipeReader, caseinfo: Case, caseid: usize, workdir: &str, output_limit: u64)-> Result<Runstate, io::Error> {
+    let memory_limit = caseinfo.memory_limit;
+    let time_limit = caseinfo.time_limit;
+    let wall_time_limit = caseinfo.wall_time_limit.unwrap_or(time_limit.saturating_mul(3));
+    let state = wait_child(child, time_limit, wall_time_limit, memory_limit)?;
+    let mut res = Runstate{ status: 1, runtime: state.cpu_time.as_micros(), wall_time: state.runtime.as_micros(), memory: exec_memory(&state, before_exec),
+                            exit_code: None, signal: None, stderr: String::new() };
+    if libc::WIFEXITED(state.status) {
+        res.exit_code = Some(libc::WEXITSTATUS(state.status));
+    } else if libc::WIFSIGNALED(state.status) {
+        res.signal = Some(libc::WTERMSIG(state.status));
+    }
+    res.stderr = captured_stderr(capture);
+    let out_size = std::fs::symlink_metadata(format!("{}/{}.out",workdir,caseid)).map(|m| m.len()).unwrap_or(0);
+    if state.time_out {
+        res.status = 2;
+    } else if state.memory_out || (memory_limit > 0 && res.memory > memory_limit) {
+        res.status = 3;
+    } else if libc::WIFSIGNALED(state.status) && libc::WTERMSIG(state.status) == libc::SIGSYS {
+        res.status = 4;
+    } else if (libc::WIFSIGNALED(state.status) && libc::WTERMSIG(state.status) == libc::SIGXFSZ)
+            || (output_limit > 0 && out_size > output_limit) {
+        res.status = 5;
+    } else if libc::WIFEXITED(state.status) && libc::WEXITSTATUS(state.status) == 0 {
+        res.status = 0;
+    }
+    Ok(res)
+}
+
+//run program with input file and return result.
+//when the problem declares input or output files, program runs in the work directory
+//and its output file is copied to {caseid}.out, so comparators read the declared file.
+//an output which is not a regular file, like a link to an answer, is taken as empty.
+fn run_test(caseinfo: Case, caseid: usize, workdir: &str, sandbox: Option<Sandbox>, output_limit: u64, misc: Option<Misc>)-> Result<Runstate, io::Error> {
+    let (input_name, output_name) = match misc {
+        Some(misc) => (misc.input_file, misc.output_file),
+        None => (None, None),
+    };
+    let (mut command, capture, before_exec) = test_command(caseinfo.clone(), workdir, sandbox, output_limit)?;
+    if input_name.is_some() || output_name.is_some() {
+        command.current_dir(workdir);
+    }
+    if input_name.is_some() {
+        io::copy(&mut File::open(caseinfo.input_file.clone())?, &mut create_work_file(&format!("{}/{}",workdir,input_name.clone().unwrap()))?)?;
+        command.stdin(process::Stdio::null());
+    } else {
+        command.stdin(process::Stdio::from(File::open(caseinfo.input_file.clone())?));
+    }
+    let out_path = format!("{}/{}.out",workdir,caseid);
+    if output_name.is_some() {
+        //output left by an earlier case must not be taken as the output of this one
+        let _ = std::fs::remove_file(format!("{}/{}",workdir,output_name.clone().unwrap()));
+        command.stdout(process::Stdio::null());
+    } else {
+        command.stdout(process::Stdio::from(create_work_file(&out_path)?));
+    }
+    let child = command.spawn()?;
+    drop(command);
+    let mut res = wait_test(&child, capture, before_exec, caseinfo, caseid, workdir, output_limit)?;
+    //the program may leave a link to an answer as its output, which is taken as writing nothing
+    if output_name.is_none() && open_work_file(&out_path).is_err() {
+        create_work_file(&out_path)?;
+    }
+    if output_name.is_some() {
+        //the output file is copied rather than renamed, so a link is never followed later,
+        //and a program which writes nothing gets an empty output
+        let output_path = format!("{}/{}",workdir,output_name.unwrap());
+        let mut output = create_work_file(&out_path)?;
+        if let Ok(mut program_output) = open_work_file(&output_path) {
+            io::copy(&mut program_output, &mut output)?;
+        }
+        let _ = std::fs::remove_file(&output_path);
+        let out_size = output.metadata()?.len();
+        if res.status == 0 && output_limit > 0 && out_size > output_limit {
+            res.status = 5;
+        }
+    }
+    Ok(res)
+}
+
+//write outputs of an output only submission to {caseid}.out in the work directory,
+//the output of a case not submitted is empty
+fn write_outputs(postjob: PostJob, problem: Problem, workdir: &str)-> Result<(), MyError> {
+    let internal_err = MyError{ code: 6, reason: "ERR_INTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() };
+    let _ = std::fs::remove_dir_all(workdir);
+    std::fs::create_dir_all(workdir).map_err(|_| internal_err.clone())?;
+    let outputs = postjob.outputs.unwrap_or_default();
+    for (caseid, caseinfo) in problem.cases.iter().enumerate() {
+        let name = caseinfo.output_name.clone().unwrap_or(format!("{}.out", caseid+1));
+        let contents = outputs.get(&name).cloned().unwrap_or_default();
+        std::fs::write(format!("{}/{}.out",workdir,caseid), contents).map_err(|_| internal_err.clone())?;
+    }
+    Ok(())
+}
+
+//result of a submitted output, which only can exceed the output limit
+fn check_output(caseid: usize, workdir: &str, output_limit: u64)-> Result<Runstate, io::Error> {
+    let out_size = open_work_file(&format!("{}/{}.out",workdir,caseid))?.metadata()?.len();
+    let mut res = Runstate{ status: 0, runtime: 0, wall_time: 0, memory: 0, exit_code: None, signal: None, stderr: String::new() };
+    if output_limit > 0 && out_size > output_limit {
+        res.status = 5;
+    }
+    Ok(res)
+}
+
+//run program together with the interactor of an interactive problem,
+//the stdout of each one is connected to the stdin of the other.
+//the interactor runs under the time limit and sandbox of special judge,
+//and reports the verdict in the testlib way.
+fn run_interactive(caseinfo: Case, caseid: usize, workdir: &str, sandbox: Option<Sandbox>, misc: Misc)-> Result<(Runstate, Result<(String, String, f64), io::Error>), io::Error> {
+    let (mut command, capture, before_exec) = test_command(caseinfo.clone(), workdir, sandbox, 0)?;
+    command.stdin(process::Stdio::piped())
+           .stdout(process::Stdio::piped());
+    let mut child = command.spawn()?;
+    drop(command);
+    let to_program = child.stdin.take().unwrap();
+    let from_program = child.stdout.take().unwrap();
+    let interactor = misc.interactor.clone().unwrap();
+    let mut comargs: Vec<String> = Vec::new();
+    for i in interactor.clone() {
+        if i == "%INPUT%" {
+            comargs.push(caseinfo.input_file.clone());
+        } else if i == "%ANSWER%" {
+            comargs.push(caseinfo.answer_file.clone());
+        } else {
+            comargs.push(i);
+        }
+    }
+    comargs.remove(0);
+    let inter_err_path = format!("{}/{}.spj.err",workdir,caseid);
+    let mut inter_command = process::Command::new(interactor[0].clone());
+    inter_command.args(comargs)
+                 .stdin(process::Stdio::from(from_program))
+                 .stdout(process::Stdio::from(to_program))
+                 .stderr(process::Stdio::from(create_work_file(&inter_err_path)?))
+                 .process_group(0);
+    let _inter_child = apply_sandbox(&mut inter_command, misc.spj_sandbox.clone(), workdir, false)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.message))
+        .and_then(|_| inter_command.spawn());
+    //the pipes held by the command must be closed, or the program never gets EOF
+    drop(inter_command);
+    let inter_child = match _inter_child {
+        Err(e) => {
+            unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL); }
+            let _ = wait_child(&child, 0, 0, 0);
+            return Err(e);
+        },
+        Ok(inter_child) => inter_child,
+    };
+    let inter_time_limit = misc.spj_time_limit.unwrap_or(10_000_000);
+    let inter_wait = std::thread::spawn(move || wait_child(&inter_child, 0, inter_time_limit, 0));
+    let res = wait_test(&child, capture, before_exec, caseinfo, caseid, workdir, 0)?;
+    let inter_state = inter_wait.join().map_err(|_| io::Error::new(io::ErrorKind::Other, ""))??;
+    if inter_state.time_out {
+        return Ok((res, Err(io::Error::new(io::ErrorKind::TimedOut, "interactor time limit exceeded"))));
+    }
+    let message = String::from_utf8_lossy(&read_work_file(&inter_err_path)?).lines().next().unwrap_or("").to_string();
+    Ok((res, testlib_verdict(inter_state.status, message)))
+}
+
+//output of a case as text, which is read without following links
+fn read_output(caseid: usize, workdir: &str)-> Result<String, io::Error> {
+    String::from_utf8(read_work_file(&format!("{}/{}.out",workdir,caseid))?)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "output is not valid utf-8"))
+}
+
+//the following 3 parts make compare between output and answer in std/strict/spj mode
+fn cmp_output_std(caseinfo: Case, caseid: usize, workdir: &str)-> Result<bool,io::Error> {
+    let out_put_file = open_work_file(&format!("{}/{}.out",workdir,caseid))?;
+    let ans_file = File::open(caseinfo.answer_file)?;
+    let output_buffer_reader = BufReader::new(out_put_file);
+    let ans_buffer_reader = BufReader::new(ans_file);
+    let mut out_put: Vec<String> = Vec::new();
+    let mut ans: Vec<String> = Vec::new();
+    //analyse file contents by lines and delete the space code on the end
+    for line in output_buffer_reader.lines() {
+        let mut read_line = line?.clone();
+            while let pop_i = read_line.pop() {
+                if pop_i.is_none(){
+                    break;
+                } else if pop_i.unwrap() != ' ' {
+                    read_line.push(pop_i.unwrap());
+                    break;
+                }
+            }
+        out_put.push(read_line);
+    }
+    for line in ans_buffer_reader.lines() {
+        let mut read_line = line?.clone();
+            while let pop_i = read_line.pop() {
+                if pop_i.is_none(){
+                    break;
+                } else if pop_i.unwrap() != ' ' {
+                    read_line.push(pop_i.unwrap());
+                    break;
+                }
+        }
+        ans.push(read_line);
+    }
+    println!("{:?}",out_put);
+    println!("{:?}",ans);
+    
+        if ans.len() > out_put.len() {
+            let m = ans.len() - out_put.len();
+            for i in 0..m {
+                out_put.push(String::from(""));
+            }
+        } else {
+            let m = out_put.len() - ans.len();
+            for i in 0..m {
+                ans.push(String::from(""));
+            }
+        }
+    let l = ans.len();
+    for i in 0..l {
+        if ans[i] != out_put[i] {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn cmp_output_strict(caseinfo: Case, caseid: usize, workdir: &str)-> Result<bool,io::Error> {
+    let output_reader = read_output(caseid, workdir)?;
+    let ans_reader = read_to_string(caseinfo.answer_file)?;
+    if ans_reader == output_reader {
+        return Ok(true);
+    } else {return Ok(false);}
+}
+
+//the following parts are built-in checkers selected by misc.checker,
+//they return whether output is accepted and where it first differs from answer
+
+//split text into tokens with their line numbers
+fn line_tokens(text: &str)-> Vec<(usize, &str)> {
+    let mut tokens: Vec<(usize, &str)> = Vec::new();
+    for (l, line) in text.lines().enumerate() {
+        for t in line.split_whitespace() {
+            tokens.push((l+1, t));
+        }
+    }
+    tokens
+}
+
+//compare output and answer token by token with the given equality
+fn cmp_tokens(caseinfo: Case, caseid: usize, workdir: &str, eq: impl Fn(&str, &str)-> bool)-> Result<(bool, String),io::Error> {
+    let output_reader = read_output(caseid, workdir)?;
+    let ans_reader = read_to_string(caseinfo.answer_file)?;
+    let out_put = line_tokens(&output_reader);
+    let ans = line_tokens(&ans_reader);
+    for i in 0..ans.len().min(out_put.len()) {
+        if !eq(out_put[i].1, ans[i].1) {
+            return Ok((false, format!("Token {} on line {} differs", i+1, out_put[i].0)));
+        }
+    }
+    if out_put.len() != ans.len() {
+        return Ok((false, format!("Expected {} tokens, found {}", ans.len(), out_put.len())));
+    }
+    Ok((true, String::new()))
+}
+
+//numbers are equal within absolute or relative error, other tokens must be the same
+fn float_eq(out_put: &str, ans: &str, epsilon: f64, relative_epsilon: f64)-> bool {
+    match (ans.parse::<f64>(), out_put.parse::<f64>()) {
+        //nan and inf have no error to measure, so they must be written the same
+        (Ok(a), Ok(o)) if a.is_finite() && o.is_finite() => {
+            let err = (a - o).abs();
+            err <= epsilon || err <= relative_epsilon * a.abs()
+        },
+        _ => ans == out_put,
+    }
+}
+
+//compare output and answer as sets of lines, blank lines and trailing spaces are ignored
+fn cmp_unordered_lines(caseinfo: Case, caseid: usize, workdir: &str)-> Result<(bool, String),io::Error> {
+    let output_reader = read_output(caseid, workdir)?;
+    let ans_reader = read_to_string(caseinfo.answer_file)?;
+    let mut out_put: Vec<&str> = output_reader.lines().map(|l| l.trim_end()).filter(|l| !l.is_empty()).collect();
+    let mut ans: Vec<&str> = ans_reader.lines().map(|l| l.trim_end()).filter(|l| !l.is_empty()).collect();
+    out_put.sort();
+    ans.sort();
+    for i in 0..ans.len().min(out_put.len()) {
+        if out_put[i] != ans[i] {
+            return Ok((false, format!("Sorted line {} differs", i+1)));
+        }
+    }
+    if out_put.len() != ans.len() {
+        return Ok((false, format!("Expected {} lines, found {}", ans.len(), out_put.len())));
+    }
+    Ok((true, String::new()))
+}
+
+//names of built-in checkers
+const CHECKERS: [&str; 4] = ["float", "tokens", "case_insensitive", "unordered_lines"];
+
+//compare output with the built-in checker set in misc, std comparison is used when not given
+fn cmp_output_checker(problem: Problem, caseinfo: Case, caseid: usize, workdir: &str)-> Result<(bool, String),io::Error> {
+    if problem.misc.is_none() || problem.misc.clone().unwrap().checker.is_none() {
+        return Ok((cmp_output_std(caseinfo, caseid, workdir)?, String::new()));
+    }
+    let misc = problem.misc.unwrap();
+    let epsilon = misc.epsilon.unwrap_or(1e-6);
+    let relative_epsilon = misc.relative_epsilon.unwrap_or(0.0);
+    match &misc.checker.unwrap() as &str {
+        "float" => cmp_tokens(caseinfo, caseid, workdir, |o, a| float_eq(o, a, epsilon, relative_epsilon)),
+        "tokens" => cmp_tokens(caseinfo, caseid, workdir, |o, a| o == a),
+        "case_insensitive" => cmp_tokens(caseinfo, caseid, workdir, |o, a| o.to_lowercase() == a.to_lowercase()),
+        "unordered_lines" => cmp_unordered_lines(caseinfo, caseid, workdir),
+        &_ => Err(io::Error::new(io::ErrorKind::Other, "unknown checker")),
+    }
+}
+
+//get verdict, info and the ratio of case score from the exit status of a testlib checker
+fn testlib_verdict(status: libc::c_int, message: String)-> Result<(String, String, f64),io::Error> {
+    if !libc::WIFEXITED(status) {
+        return Err(io::Error::new(io::ErrorKind::Other, ""));
+    }
+    match libc::WEXITSTATUS(status) {
+        0 => Ok(("Accepted".to_string(), message, 1.0)),
+        1 => Ok(("Wrong Answer".to_string(), message, 0.0)),
+        2 => Ok(("Presentation Error".to_string(), message, 0.0)),
+        7 => {
+            let mut words = message.split_whitespace();
+            let mut first = words.next().unwrap_or("");
+            if first == "points" {
+                first = words.next().unwrap_or("");
+            }
+            let ratio = first.parse::<f64>().map_err(|_| io::Error::new(io::ErrorKind::Other, ""))?.clamp(0.0, 1.0);
+            if ratio >= 1.0 {
+                return Ok(("Accepted".to_string(), message, 1.0));
+            }
+            Ok(("Partially Correct".to_string(), message, ratio))
+        },
+        _ => Err(io::Error::new(io::ErrorKind::Other, "")),
+    }
+}
+
+//run special judge under its time limit and sandbox, and get verdict, info and the ratio of case score.
+//legacy checkers print the verdict on the first line of stdout and info on the second line,
+//testlib checkers report by exit code: 0 accepted, 1 wrong answer, 2 presentation error,
+//7 points, where the message in stderr starts with the ratio of score, like "points 0.5 ok".
+fn special_judge(caseinfo: Case, caseid: usize, misc: Misc, workdir: &str)-> Result<(String, String, f64),io::Error> {
+    let spj = misc.special_judge.clone().unwrap();
+    let mut comargs: Vec<String> = Vec::new();
+    for i in spj.clone() {
+        if i == "%INPUT%" {
+            comargs.push(caseinfo.input_file.clone());
+        } else if i == "%ANSWER%" {
+            comargs.push(caseinfo.answer_file.clone());
+        } else if i == "%OUTPUT%" {
+            //the output is checked to be a regular file before the special judge reads it
+            open_work_file(&format!("{}/{}.out",workdir,caseid))?;
+            comargs.push(format!("{}/{}.out",workdir,caseid));
+        } else {
+            comargs.push(i);
+        }
+    }
+    comargs.remove(0);
+    //run spj and get the result
+    let spj_out_path = format!("{}/{}.spj.out",workdir,caseid);
+    let spj_err_path = format!("{}/{}.spj.err",workdir,caseid);
+    let mut command = process::Command::new(spj[0].clone());
+    command.args(comargs)
+           .stdin(process::Stdio::null())
+           .stdout(process::Stdio::from(create_work_file(&spj_out_path)?))
+           .stderr(process::Stdio::from(create_work_file(&spj_err_path)?))
+           .process_group(0);
+    if let Err(e) = apply_sandbox(&mut command, misc.spj_sandbox.clone(), workdir, false) {
+        return Err(io::Error::new(io::ErrorKind::Other, e.message));
+    }
+    let child = command.spawn()?;
+    let state = wait_child(&child, 0, misc.spj_time_limit.unwrap_or(10_000_000), 0)?;
+    if state.time_out {
+        return Err(io::Error::new(io::ErrorKind::TimedOut, "special judge time limit exceeded"));
+    }
+    let _spj_out = String::from_utf8(read_work_file(&spj_out_path)?).map_err(|_| io::Error::new(io::ErrorKind::Other, ""))?;
+    let _spj_err = String::from_utf8_lossy(&read_work_file(&spj_err_path)?).to_string();
+    if misc.spj_protocol.as_deref() == Some("testlib") {
+        return testlib_verdict(state.status, _spj_err.lines().next().unwrap_or("").to_string());
+    }
+    let spj_out: Vec<&str> = _spj_out.split("\n").collect();
+    let b = spj_out[0].to_string();
+    if b != "Accepted" && b != "Wrong Answer" && b != "Time Limit Exceeded" && b != "Memory Limit Exceeded" && b != "Output Limit Exceeded" && b != "Runtime Error" && b != "System Error" {
+        return Err(io::Error::new(io::ErrorKind::Other, ""));
+    }
+    let info = spj_out.get(1).unwrap_or(&"").to_string();
+    if b == "Accepted" {
+        return Ok((b, info, 1.0));
+    } else {
+        return Ok((b, info, 0.0));
+    }
+}
+
+//check if the problem settings needed in judging are available
+fn check_problem_type(problem: Problem)-> Option<MyError> {
+    if problem.subtasks.is_some() {
+        let subtasks = problem.subtasks.clone().unwrap();
+        for (k, subtask) in subtasks.iter().enumerate() {
+            let cases_ok = subtask.cases.iter().all(|&c| c >= 1 && c <= problem.cases.len());
+            let depends_ok = subtask.depends.clone().unwrap_or_default().iter().all(|&d| d >= 1 && d <= k);
+            let aggregation_ok = ["all", "min", "product"].contains(&subtask.aggregation.as_deref().unwrap_or("all"));
+            if !cases_ok || !depends_ok || !aggregation_ok {
+                return Some(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: format!("Invalid subtask {}", k+1) });
+            }
+        }
+    }
+    if problem.misc.is_some() && problem.misc.clone().unwrap().checker.is_some() {
+        let checker = problem.misc.clone().unwrap().checker.unwrap();
+        if !CHECKERS.contains(&checker.as_str()) {
+            return Some(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: format!("Checker {} Not Found",checker) });
+        }
+    }
+    if problem.misc.is_some() {
+        let misc = problem.misc.clone().unwrap();
+        //the first word of a command is the program to run
+        if misc.special_judge.as_ref().is_some_and(|c| c.is_empty()) || misc.interactor.as_ref().is_some_and(|c| c.is_empty()) {
+            return Some(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "Empty command of special judge or interactor".to_string() });
+        }
+        for name in [misc.input_file, misc.output_file].into_iter().flatten() {
+            //the files must stay in the work directory and not replace files of the judge
+            if name.is_empty() || name.contains('/') || name == "." || name == ".." || name == "out_put_program" || problem.ty == "interactive" {
+                return Some(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: format!("Invalid file name {}",name) });
+            }
+        }
+    }
+    match &problem.ty as &str {
+        "standard" | "strict" | "output_only" => None,
+        "dynamic_ranking" => {
+            let ratio = problem.misc.and_then(|misc| misc.dynamic_ranking_ratio);
+            if !ratio.is_some_and(|r| (0.0..=1.0).contains(&r)) {
+                return Some(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "Invalid dynamic ranking ratio".to_string() });
+            }
+            None
+        },
+        "spj" => {
+            if problem.misc.is_none() || problem.misc.unwrap().special_judge.is_none() {
+                return Some(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "HTTP 400 Bad Request".to_string() });
+            }
+            None
+        },
+        "interactive" => {
+            if problem.misc.is_none() || problem.misc.unwrap().interactor.is_none() {
+                return Some(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "HTTP 400 Bad Request".to_string() });
+            }
+            None
+        },
+        &_ => Some(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "HTTP 400 Bad Request".to_string() }),
+    }
+}
+
+//results of subtasks before judging
+fn waiting_subtasks(problem: Problem)-> Option<Vec<SubtaskResult>> {
+    let subtasks = problem.subtasks?;
+    let mut res: Vec<SubtaskResult> = Vec::new();
+    for i in 0..subtasks.len() {
+        res.push(SubtaskResult{ id: i+1, result: "Waiting".to_string(), score: 0.0 });
+    }
+    Some(res)
+}
+
+fn this_language(config: web::Data<Config>, name: String)-> Option<Language> {
+    for i in config.languages.clone() {
+        if i.name == name {
+            return Some(i.clone());
+        }
+    }
+    None
+}
+
+//judging part
+//jobs are judged in background so that requests return at once.
+//post /jobs and put /jobs save a job in "Queueing" and push its id into JOB_QUEUE,
+//then a judge worker takes it out, moves it to "Running" and "Finished",
+//and updates the job in JOB_LIST after every case so that get /jobs/{id} shows the progress.
+//several workers can judge at the same time, each job is built and run in its own
+//work directory {WORK_ROOT}/job_{id} which is removed after judging.
+
+//push a job id into the queue and wake up the worker
+fn enqueue_job(job_id: usize) {
+    let (queue, cvar) = &**JOB_QUEUE;
+    queue.lock().unwrap().push_back(job_id);
+    cvar.notify_one();
+}
+
+//save the judging progress of a job
+fn update_job(job_res: &Job) {
+    //a remote worker reports progress to its server instead
+    if let Some((server, worker_id, token)) = REMOTE_WORKER.with(|remote| remote.borrow().clone()) {
+        let _ = report_job(&server, worker_id, &token, job_res);
+        return;
+    }
+    let mut job_list = JOB_LIST.lock().unwrap();
+    //the record of a canceled job is not changed by its worker any more
+    if CANCELED_JOBS.lock().unwrap().contains(&job_res.id) {
+        return;
+    }
+    publish_job_events(Some(&job_list[job_res.id]), job_res);
+    job_list[job_res.id] = job_res.clone();
+}
+
+//send events of changes from old to new record of a job,
+//it is called with JOB_LIST locked so events of a job are in order
+fn publish_job_events(old: Option<&Job>, new: &Job) {
+    let send = |event: &str, data: String, last: bool| {
+        //there is no error but having no stream
+        let _ = JOB_EVENTS.send(JobEvent{ job_id: new.id, contest_id: new.submission.contest_id, event: event.to_string(), data: data, last: last });
+    };
+    for (i, case) in new.cases.iter().enumerate() {
+        let old_result = old.and_then(|o| o.cases.get(i)).map(|c| c.result.clone());
+        if case.result != "Waiting" && case.result != "Running" && old_result.as_ref() != Some(&case.result) {
+            send("case", serde_json::json!({ "job_id": new.id, "case": case }).to_string(), false);
+        }
+    }
+    if old.map(|o| o.state) == Some(new.state) {
+        return;
+    }
+    let last = new.state != JobState::Queueing && new.state != JobState::Running;
+    send("state", serde_json::json!({ "job_id": new.id, "state": new.state, "result": new.result, "score": new.score }).to_string(), last);
+    if last {
+        send("verdict", serde_json::json!({ "job_id": new.id, "user_id": new.submission.user_id, "contest_id": new.submission.contest_id,
+                                            "problem_id": new.submission.problem_id, "state": new.state, "result": new.result, "score": new.score }).to_string(), false);
+    }
+}
+
+//whether the job judged by this thread is canceled
+fn current_job_canceled()-> bool {
+    match CURRENT_JOB.with(|job| job.get()) {
+        Some(job_id) => CANCELED_JOBS.lock().unwrap().contains(&job_id),
+        None => false,
+    }
+}
+
+//wait for jobs in queue and judge them one by one, every worker runs in a thread
+fn judge_worker(config: web::Data<Config>) {
+    loop {
+        let job_id;
+        {
+            let (queue, cvar) = &**JOB_QUEUE;
+            let mut waiting = queue.lock().unwrap();
+            while waiting.is_empty() {
+                waiting = cvar.wait(waiting).unwrap();
+            }
+            job_id = waiting.pop_front().unwrap();
+        }
+        judge_job(job_id, config.clone());
+    }
+}
+
+//judge a job from the queue and save the result
+fn judge_job(job_id: usize, config: web::Data<Config>) {
+    if let Some(mut job_res) = start_job(job_id) {
+        execute_job(&mut job_res, config.clone());
+        finish_job(job_res, config);
+    }
+}
+
+//set a queued job running, the job may be canceled after it is taken from queue
+fn start_job(job_id: usize)-> Option<Job> {
+    start_listed_job(&mut JOB_LIST.lock().unwrap(), job_id)
+}
+
+fn start_listed_job(job_list: &mut Vec<Job>, job_id: usize)-> Option<Job> {
+    if job_list[job_id].state != JobState::Queueing {
+        return None;
+    }
+    let mut job_res = job_list[job_id].clone();
+    job_res.state = JobState::Running;
+    job_res.updated_time = Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
+    publish_job_events(Some(&job_list[job_id]), &job_res);
+    job_list[job_id] = job_res.clone();
+    Some(job_res)
+}
+
+//judge a job in this thread, used by both local and remote workers
+fn execute_job(job_res: &mut Job, config: web::Data<Config>) {
+    CURRENT_JOB.with(|job| job.set(Some(job_res.id)));
+    let workdir = format!("{}/job_{}", WORK_ROOT.lock().unwrap(), job_res.id);
+    job_res.state = match run_job(job_res, config.clone(), &workdir) {
+        Ok(()) => JobState::Finished,
+        Err(e) => {
+            log::error!(target: "judge_worker", "Job {} failed: {}", job_res.id, e.message);
+            job_res.result = "System Error".to_string();
+            JobState::Failed
+        },
+    };
+    CURRENT_JOB.with(|job| job.set(None));
+    let _ = std::fs::remove_dir_all(&workdir);
+}
+
+//save a judged job and tell webhooks, return false when the job is canceled
+fn finish_job(mut job_res: Job, config: web::Data<Config>)-> bool {
+    let job_id = job_res.id;
+    let mut job_list = JOB_LIST.lock().unwrap();
+    if CANCELED_JOBS.lock().unwrap().remove(&job_id) {
+        //the record is saved by the request which cancels the job
+        return false;
+    }
+    job_res.updated_time = Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
+    if data_update("jobs".to_string(), job_id, to_string_pretty(&job_res).unwrap()).is_err() {
+        log::error!(target: "judge_worker", "Job {} can not be saved", job_id);
+    }
+    publish_job_events(Some(&job_list[job_id]), &job_res);
+    job_list[job_id] = job_res.clone();
+    drop(job_list);
+    send_webhooks(job_res, config);
+    true
+}
+
+//hmac with sha256 (RFC 2104)
+fn hmac_sha256(key: &[u8], message: &[u8])-> Vec<u8> {
+    let mut block = [0u8; 64];
+    if key.len() > 64 {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner = Sha256::new();
+    inner.update(block.map(|b| b ^ 0x36));
+    inner.update(message);
+    let mut outer = Sha256::new();
+    outer.update(block.map(|b| b ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().to_vec()
+}
+
+//post a judged job to webhooks and its callback url, each of them is delivered in a thread
+fn send_webhooks(job_res: Job, config: web::Data<Config>) {
+    let webhooks = config.server.webhooks.clone().unwrap_or(Webhooks{ urls: Vec::new(), secret: None, retries: None, callback_hosts: Vec::new() });
+    let mut urls = webhooks.urls.clone();
+    //the config may have changed since the job was posted
+    if let Some(url) = job_res.submission.callback_url.clone().filter(|url| callback_allowed(url, &config)) {
+        urls.push(url);
+    }
+    if urls.is_empty() {
+        return;
+    }
+    let body = serde_json::to_string(&job_res).unwrap();
+    let signature = webhooks.secret.map(|secret| hmac_sha256(secret.as_bytes(), body.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect::<String>());
+    let retries = webhooks.retries.unwrap_or(3);
+    for url in urls {
+        let body = body.clone();
+        let signature = signature.clone();
+        std::thread::spawn(move || deliver_webhook(url, body, signature, retries));
+    }
+}
+
+//whether a callback url is http(s) to a host listed in callback_hosts, so that jobs can not make
+//the server post to itself or to other private services
+fn callback_allowed(url: &str, config: &web::Data<Config>)-> bool {
+    let rest = match url.strip_prefix("http://").or_else(|| url.strip_prefix("https://")) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    //the host is what is left of the authority without user info and port
+    let authority = rest.split(|c| c == '/' || c == '\\' || c == '?' || c == '#').next().unwrap_or("");
+    let host_port = authority.rsplit('@').next().unwrap_or("");
+    let host = if host_port.starts_with('[') {
+        host_port.split(']').next().unwrap_or("").to_string() + "]"
+    } else {
+        host_port.split(':').next().unwrap_or("").to_string()
+    };
+    let hosts = config.server.webhooks.as_ref().map(|webhooks| webhooks.callback_hosts.clone()).unwrap_or_default();
+    !host.is_empty() && hosts.iter().any(|allowed| allowed.eq_ignore_ascii_case(&host))
+}
+
+//post to a webhook until it answers with 2xx, waiting 1, 2, 4... seconds between tries,
+//redirects are not followed as they could lead anywhere
+fn deliver_webhook(url: String, body: String, signature: Option<String>, retries: u32) {
+    let agent = ureq::AgentBuilder::new().redirects(0).build();
+    for attempt in 0..=retries {
+        let mut request = agent.post(&url)
+            .set("Content-Type", "application/json")
+            .timeout(std::time::Duration::from_secs(10));
+        if let Some(signature) = signature.as_ref() {
+            request = request.set("X-OJ-Signature", &format!("sha256={}", signature));
+        }
+        match request.send_string(&body) {
+            Ok(_) => return,
+            Err(e) => log::warn!(target: "webhook", "Delivery to {} failed: {}", url, e),
+        }
+        if attempt < retries {
+            std::thread::sleep(std::time::Duration::from_secs(1 << attempt.min(10)));
+        }
+    }
+}
+
+//build the program and run all cases of a job
+//cases of a problem with subtasks are judged by subtask, see judge_subtasks
+fn run_job(job_res: &mut Job, config: web::Data<Config>, workdir: &str)-> Result<(), MyError> {
+    let postjob = job_res.submission.clone();
+    let mut problem = this_problem(postjob.problem_id)
+        .ok_or(MyError{ code: 3, reason: "ERR_NOT_FOUND".to_string(), message: format!("Problem {} Not Found",postjob.problem_id) })?;
+    //the problem may have changed since the job was queued, so results are laid out by it as it is now
+    job_res.cases.clear();
+    for i in 0..=problem.cases.len() {
+        job_res.cases.push( CaseResult{ id: i, result: "Waiting".to_string(), time: 0, wall_time: 0, memory: 0, info: "".to_string(), exit_code: None, signal: None } );
+    }
+    job_res.subtasks = waiting_subtasks(problem.clone());
+    let mut sandbox = None;
+    if problem.ty == "output_only" {
+        //nothing to build, outputs are checked directly
+        write_outputs(postjob.clone(), problem.clone(), workdir)?;
+        job_res.cases[0].result = "Skipped".to_string();
+    } else {
+        let langu = this_language(config.clone(), postjob.language.clone())
+            .ok_or(MyError{ code: 3, reason: "ERR_NOT_FOUND".to_string(), message: format!("Language {} Not Found",postjob.language) })?;
+        //build program
+        let (status, compile_info) = cached_build(postjob.clone(), langu.clone(), workdir, config.clone())?;
+        job_res.cases[0].info = compile_info;
+        if !status.success() {
+            job_res.result = String::from("Compilation Error");
+            job_res.cases[0].result = "Compilation Error".to_string();
+            return Ok(());
+        }
+        job_res.cases[0].result = "Compilation Success".to_string();
+        sandbox = langu.sandbox.clone();
+        problem = language_limits(problem, langu);
+    }
+    update_job(job_res);
+    let mut dy_ratio: f64 = 0.0;
+    if problem.ty == "dynamic_ranking" && problem.misc.is_some() && problem.misc.clone().unwrap().dynamic_ranking_ratio.is_some() {
+        dy_ratio = problem.misc.clone().unwrap().dynamic_ranking_ratio.unwrap();
+    }
+    if problem.subtasks.is_some() {
+        judge_subtasks(job_res, problem.clone(), sandbox.clone(), workdir, dy_ratio)?;
+    } else {
+        for i in 0..problem.cases.len() {
+            let ratio = judge_case(job_res, problem.clone(), sandbox.clone(), i, workdir)?;
+            job_res.score += problem.cases[i].score * ratio * (1.0-dy_ratio);
+            update_job(job_res);
+        }
+    }
+    for i in 1..job_res.cases.len() {
+        if job_res.cases[i].result != "Accepted" && job_res.cases[i].result != "Skipped" {
+            job_res.result = job_res.cases[i].result.clone();
+            break;
+        }
+    }
+    if job_res.result == "Waiting" { job_res.result = "Accepted".to_string(); }
+    Ok(())
+}
+
+//scale limits of cases for a language, limits of 0 stay unlimited
+fn language_limits(mut problem: Problem, langu: Language)-> Problem {
+    let scale = |limit: u64, multiplier: Option<f64>, extra: Option<u64>| {
+        if limit == 0 {
+            return 0;
+        }
+        ((limit as f64 * multiplier.unwrap_or(1.0)) as u64).saturating_add(extra.unwrap_or(0))
+    };
+    for caseinfo in problem.cases.iter_mut() {
+        //wall time limit follows cpu time limit when not given, so it is scaled only when given
+        caseinfo.wall_time_limit = caseinfo.wall_time_limit.map(|limit| scale(limit, langu.time_multiplier, langu.extra_time));
+        caseinfo.time_limit = scale(caseinfo.time_limit, langu.time_multiplier, langu.extra_time);
+        caseinfo.memory_limit = scale(caseinfo.memory_limit, langu.memory_multiplier, langu.extra_memory);
+    }
+    problem
+}
+
+//judge subtasks in order, a subtask is skipped unless all subtasks it depends on are accepted,
+//and the rest cases of a subtask are skipped once its score becomes 0.
+//a case shared by several subtasks is judged only once.
+fn judge_subtasks(job_res: &mut Job, problem: Problem, sandbox: Option<Sandbox>, workdir: &str, dy_ratio: f64)-> Result<(), MyError> {
+    let subtasks = problem.subtasks.clone().unwrap();
+    let mut case_ratio: Vec<Option<f64>> = Vec::new();
+    case_ratio.resize(problem.cases.len(), None);
+    let mut subtask_ratio: Vec<f64> = Vec::new();
+    for (k, subtask) in subtasks.iter().enumerate() {
+        let mut ratio: f64 = 1.0;
+        let mut result = "Accepted".to_string();
+        for d in subtask.depends.clone().unwrap_or_default() {
+            if subtask_ratio[d-1] < 1.0 {
+                ratio = 0.0;
+                result = "Skipped".to_string();
+                break;
+            }
+        }
+        for c in subtask.cases.clone() {
+            if ratio == 0.0 {
+                break;
+            }
+            if case_ratio[c-1].is_none() {
+                case_ratio[c-1] = Some(judge_case(job_res, problem.clone(), sandbox.clone(), c-1, workdir)?);
+            }
+            let r = case_ratio[c-1].unwrap();
+            match subtask.aggregation.as_deref().unwrap_or("all") {
+                "min" => ratio = ratio.min(r),
+                "product" => ratio *= r,
+                _ => if r < 1.0 { ratio = 0.0; },
+            }
+            if r < 1.0 && result == "Accepted" {
+                result = job_res.cases[c].result.clone();
+            }
+        }
+        if ratio > 0.0 && ratio < 1.0 {
+            result = "Partially Correct".to_string();
+        }
+        subtask_ratio.push(ratio);
+        //the rest of the score of dynamic ranking problems is given in ranking
+        job_res.score += subtask.score * ratio * (1.0-dy_ratio);
+        if let Some(subtask_res) = job_res.subtasks.as_mut() {
+            subtask_res[k] = SubtaskResult{ id: k+1, result: result, score: subtask.score * ratio * (1.0-dy_ratio) };
+        }
+        update_job(job_res);
+    }
+    for i in 0..problem.cases.len() {
+        if case_ratio[i].is_none() {
+            job_res.cases[i+1].result = "Skipped".to_string();
+        }
+    }
+    Ok(())
+}
+
+//run case i of a problem and save its result in job, return the ratio of case score it gets
+fn judge_case(job_res: &mut Job, problem: Problem, sandbox: Option<Sandbox>, i: usize, workdir: &str)-> Result<f64, MyError> {
+    job_res.cases[i+1].result = "Running".to_string();
+    update_job(job_res);
+    //run program
+    let mut output_limit = 0;
+    if problem.cases[i].output_limit.is_some() {
+        output_limit = problem.cases[i].output_limit.unwrap();
+    } else if problem.misc.is_some() && problem.misc.clone().unwrap().output_limit.is_some() {
+        output_limit = problem.misc.clone().unwrap().output_limit.unwrap();
+    }
+    let mut interact_res = None;
+    let _state = if problem.ty == "output_only" {
+        check_output(i, workdir, output_limit)
+    } else if problem.ty == "interactive" {
+        run_interactive(problem.cases[i].clone(), i, workdir, sandbox, problem.clone().misc.unwrap())
+            .map(|(state, verdict)| { interact_res = Some(verdict); state })
+    } else {
+        run_test(problem.cases[i].clone(), i, workdir, sandbox, output_limit, problem.misc.clone())
+    };
+    let state = match _state {
+        Err(_e) => return Err(MyError{ code: 6, reason: "ERR_INTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() }),
+        Ok(state) => state,
+    };
+    job_res.cases[i+1].time = state.runtime;
+    job_res.cases[i+1].wall_time = state.wall_time;
+    job_res.cases[i+1].memory = state.memory;
+    job_res.cases[i+1].exit_code = state.exit_code;
+    job_res.cases[i+1].signal = state.signal.map(signal_name);
+    if problem.misc.is_some() && problem.misc.clone().unwrap().show_stderr.unwrap_or(false) {
+        job_res.cases[i+1].info = state.stderr.clone();
+    }
+    let mut ratio: f64 = 0.0;
+    // check the answer
+    match state.status {
+        0 => {
+            if state.memory > problem.cases[i].memory_limit && problem.cases[i].memory_limit > 0 {
+                job_res.cases[i+1].result = "Memory Limit Exceeded".to_string();
+            } else {
+                //output only problems are checked by special judge when it is given, or by checker
+                let mut mode = problem.ty.as_str();
+                if mode == "output_only" {
+                    mode = if problem.misc.is_some() && problem.misc.clone().unwrap().special_judge.is_some() { "spj" } else { "standard" };
+                }
+                //run compare programs
+                match mode {
+                    "standard" | "dynamic_ranking" => match cmp_output_checker(problem.clone(), problem.cases[i].clone(), i, workdir) {
+                        Ok((b, info)) => {
+                            if !info.is_empty() {
+                                job_res.cases[i+1].info = info;
+                            }
+                            if b {
+                                job_res.cases[i+1].result = "Accepted".to_string();
+                                ratio = 1.0;
+                            } else {
+                                job_res.cases[i+1].result = "Wrong Answer".to_string();
+                            }
+                        },
+                        Err(_e) => return Err(MyError{ code: 5, reason: "ERR_EXTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() }),
+                    },
+                    "strict" => match cmp_output_strict(problem.cases[i].clone(), i, workdir) {
+                        Ok(b) => {
+                            if b {
+                                job_res.cases[i+1].result = "Accepted".to_string();
+                                ratio = 1.0;
+                            } else {
+                                job_res.cases[i+1].result = "Wrong Answer".to_string();
+                            }
+                        },
+                        Err(_e) => return Err(MyError{ code: 5, reason: "ERR_EXTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() }),
+                    },
+                    "spj" => {
+                        //spj settings are checked before the job is queued
+                        match special_judge(problem.cases[i].clone(), i, problem.clone().misc.unwrap(), workdir) {
+                            Err(_e) => {
+                                job_res.cases[i+1].result = "SPJ Error".to_string();
+                            },
+                            Ok(b) => {
+                                job_res.cases[i+1].result = b.0.clone();
+                                job_res.cases[i+1].info = b.1.clone();
+                                ratio = b.2;
+                            }
+                        }
+                    },
+                    "interactive" => {
+                        match interact_res.take().unwrap() {
+                            Err(_e) => {
+                                job_res.cases[i+1].result = "SPJ Error".to_string();
+                            },
+                            Ok(b) => {
+                                job_res.cases[i+1].result = b.0.clone();
+                                job_res.cases[i+1].info = b.1.clone();
+                                ratio = b.2;
+                            }
+                        }
+                    },
+                    &_ => return Err(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "HTTP 400 Bad Request".to_string() }),
+                };
+            }
+        },
+        status => match status_result(status) {
+            Some(result) => job_res.cases[i+1].result = result,
+            None => return Err(MyError{ code: 6, reason: "ERR_INTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() }),
+        },
+    }
+    update_job(job_res);
+    Ok(ratio)
+}
+//judging part end
+
+//remote worker part
+//judge nodes run this program with --worker and pull jobs from the server over http:
+//POST /workers registers a node with its languages, POST /workers/{id}/claim takes a queued job,
+//PUT /workers/{id}/jobs/{job_id} reports progress and the result, and POST /workers/{id}/heartbeat keeps the lease.
+//a node missing heartbeats for the lease is removed and its job is queued again.
+//every request of a node carries the worker_token of the config as "Authorization: Bearer <token>".
+//problems are fetched from the server before judging and languages are read from the config of the node.
+//each node process judges in tmp/worker_{pid}, so it never touches the server or other nodes on the same host.
+//data files uploaded to the server are downloaded to the same paths on the node when they change,
+//other data files must be at the same paths on the node.
+
+//refuse requests of workers without the worker token, all workers are refused when the server has none
+fn check_worker_token(req: &actix_web::HttpRequest, config: &web::Data<Config>)-> Option<HttpResponse> {
+    check_token(req, config.server.worker_token.clone(), "worker")
+}
+
+//refuse requests without "Authorization: Bearer <token>", all requests are refused when the token is not set
+fn check_token(req: &actix_web::HttpRequest, token: Option<String>, name: &str)-> Option<HttpResponse> {
+    let expected = token.unwrap_or_default();
+    let given = req.headers().get("Authorization").and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer ")).unwrap_or("");
+    //compared in constant time so that the token can not be guessed byte by byte
+    let differs = expected.len() != given.len()
+        || expected.bytes().zip(given.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) != 0;
+    if expected.is_empty() || differs {
+        return Some(actix_web::HttpResponse::Forbidden()
+            .json(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: format!("Invalid {} token",name) }));
+    }
+    None
+}
+
+//add the worker token of the config to a request to the server
+fn with_worker_token(request: ureq::Request, token: &str)-> ureq::Request {
+    request.set("Authorization", &format!("Bearer {}", token))
+}
+
+#[post("/workers")]
+async fn post_workers(req: actix_web::HttpRequest, postworker: web::Json<PostWorker>, config: web::Data<Config>)-> impl Responder {
+    if let Some(refused) = check_worker_token(&req, &config) {
+        return refused;
+    }
+    let worker_id = {
+        let mut worker_count = WORKER_COUNT.lock().unwrap();
+        *worker_count += 1;
+        *worker_count - 1
+    };
+    let worker = Worker{ id: worker_id, name: postworker.name.clone(), languages: postworker.languages.clone(), job_id: None,
+                         lease: config.server.worker_lease.unwrap_or(30), last_seen: std::time::Instant::now() };
+    log::info!(target: "remote_worker", "Worker {} ({}) registered", worker_id, worker.name);
+    WORKERS.lock().unwrap().insert(worker_id, worker.clone());
+    actix_web::HttpResponse::Ok().json(worker)
+}
+
+#[get("/workers")]
+async fn get_workers(req: actix_web::HttpRequest, config: web::Data<Config>)-> impl Responder {
+    if let Some(refused) = check_worker_token(&req, &config) {
+        return refused;
+    }
+    let mut workers: Vec<Worker> = WORKERS.lock().unwrap().values().cloned().collect();
+    workers.sort_by_key(|worker| worker.id);
+    actix_web::HttpResponse::Ok().json(workers)
+}
+
+fn worker_not_found(worker_id: usize)-> HttpResponse {
+    actix_web::HttpResponse::NotFound()
+        .json(MyError{ code: 3, reason: "ERR_NOT_FOUND".to_string(), message: format!("Worker {} not found.",worker_id) })
+}
+
+//take the first queued job the worker can judge, no content when there is none
+#[post("/workers/{worker_id}/claim")]
+async fn claim_job(req: actix_web::HttpRequest, worker_id: web::Path<usize>, config: web::Data<Config>)-> impl Responder {
+    if let Some(refused) = check_worker_token(&req, &config) {
+        return refused;
+    }
+    let worker_id = worker_id.into_inner();
+    let mut job_list = JOB_LIST.lock().unwrap();
+    let mut workers = WORKERS.lock().unwrap();
+    let worker = match workers.get_mut(&worker_id) {
+        None => return worker_not_found(worker_id),
+        Some(worker) => worker,
+    };
+    worker.last_seen = std::time::Instant::now();
+    if worker.job_id.is_some() {
+        return actix_web::HttpResponse::BadRequest()
+            .json(MyError{ code: 2, reason: "ERR_INVALID_STATE".to_string(), message: format!("Worker {} is judging job {}.",worker_id,worker.job_id.unwrap()) });
+    }
+    let mut queue = JOB_QUEUE.0.lock().unwrap();
+    //output only problems need no language
+    let position = queue.iter().position(|&job_id| {
+        let submission = &job_list[job_id].submission;
+        worker.languages.contains(&submission.language)
+            || this_problem(submission.problem_id).map(|problem| problem.ty == "output_only").unwrap_or(false)
+    });
+    let job_id = match position {
+        None => return actix_web::HttpResponse::NoContent().finish(),
+        Some(position) => queue.remove(position).unwrap(),
+    };
+    drop(queue);
+    match start_listed_job(&mut job_list, job_id) {
+        None => actix_web::HttpResponse::NoContent().finish(),
+        Some(job_res) => {
+            worker.job_id = Some(job_id);
+            actix_web::HttpResponse::Ok().json(job_res)
+        },
+    }
+}
+
+//progress and result of a job from its worker, a canceled job is answered with ERR_INVALID_STATE
+#[put("/workers/{worker_id}/jobs/{job_id}")]
+async fn put_worker_job(req: actix_web::HttpRequest, path: web::Path<(usize, usize)>, report: web::Json<Job>, config: web::Data<Config>)-> impl Responder {
+    if let Some(refused) = check_worker_token(&req, &config) {
+        return refused;
+    }
+    let (worker_id, job_id) = path.into_inner();
+    let report = report.into_inner();
+    {
+        let mut workers = WORKERS.lock().unwrap();
+        let worker = match workers.get_mut(&worker_id) {
+            None => return worker_not_found(worker_id),
+            Some(worker) => worker,
+        };
+        if worker.job_id != Some(job_id) {
+            return actix_web::HttpResponse::BadRequest()
+                .json(MyError{ code: 2, reason: "ERR_INVALID_STATE".to_string(), message: format!("Worker {} is not judging job {}.",worker_id,job_id) });
+        }
+        worker.last_seen = std::time::Instant::now();
+        let finished = report.state != JobState::Queueing && report.state != JobState::Running;
+        if finished || CANCELED_JOBS.lock().unwrap().contains(&job_id) {
+            worker.job_id = None;
         }
-        ans.push(read_line);
     }
-    println!("{:?}",out_put);
-    println!("{:?}",ans);
-    
-        if ans.len() > out_put.len() {
-            let m = ans.len() - out_put.len();
-            for i in 0..m {
-                out_put.push(String::from(""));
+    let canceled = actix_web::HttpResponse::BadRequest()
+        .json(MyError{ code: 2, reason: "ERR_INVALID_STATE".to_string(), message: format!("Job {} is canceled.",job_id) });
+    //only results are taken from the worker
+    let mut job_res = JOB_LIST.lock().unwrap()[job_id].clone();
+    job_res.result = report.result;
+    job_res.score = report.score;
+    job_res.cases = report.cases;
+    job_res.subtasks = report.subtasks;
+    if report.state == JobState::Finished || report.state == JobState::Failed {
+        job_res.state = report.state;
+        if !finish_job(job_res.clone(), config) {
+            return canceled;
+        }
+        return actix_web::HttpResponse::Ok().json(job_res);
+    }
+    if CANCELED_JOBS.lock().unwrap().remove(&job_id) {
+        return canceled;
+    }
+    update_job(&job_res);
+    actix_web::HttpResponse::Ok().json(job_res)
+}
+
+//keep the lease of a worker, the answer tells whether its job is canceled
+#[post("/workers/{worker_id}/heartbeat")]
+async fn worker_heartbeat(req: actix_web::HttpRequest, worker_id: web::Path<usize>, config: web::Data<Config>)-> impl Responder {
+    if let Some(refused) = check_worker_token(&req, &config) {
+        return refused;
+    }
+    let worker_id = worker_id.into_inner();
+    let mut workers = WORKERS.lock().unwrap();
+    let worker = match workers.get_mut(&worker_id) {
+        None => return worker_not_found(worker_id),
+        Some(worker) => worker,
+    };
+    worker.last_seen = std::time::Instant::now();
+    let canceled = worker.job_id.map(|job_id| CANCELED_JOBS.lock().unwrap().contains(&job_id)).unwrap_or(false);
+    actix_web::HttpResponse::Ok().json(serde_json::json!({ "canceled": canceled }))
+}
+
+//remove workers missing heartbeats and queue their jobs again, it runs in a thread of the server
+fn reap_workers(config: web::Data<Config>) {
+    let lease = std::time::Duration::from_secs(config.server.worker_lease.unwrap_or(30));
+    loop {
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        let mut lost_jobs: Vec<usize> = Vec::new();
+        WORKERS.lock().unwrap().retain(|_, worker| {
+            if worker.last_seen.elapsed() <= lease {
+                return true;
             }
-        } else {
-            let m = out_put.len() - ans.len();
-            for i in 0..m {
-                ans.push(String::from(""));
+            log::warn!(target: "remote_worker", "Worker {} ({}) lost", worker.id, worker.name);
+            lost_jobs.extend(worker.job_id);
+            false
+        });
+        for job_id in lost_jobs {
+            //a canceled job is not judged again
+            if CANCELED_JOBS.lock().unwrap().remove(&job_id) {
+                continue;
+            }
+            let job_res = JOB_LIST.lock().unwrap()[job_id].clone();
+            match this_problem(job_res.submission.problem_id) {
+                Some(problem) => {
+                    if requeue_job(job_id, problem, false).is_err() {
+                        log::error!(target: "remote_worker", "Job {} can not be queued again", job_id);
+                    }
+                },
+                None => log::error!(target: "remote_worker", "Job {} can not be queued again", job_id),
             }
         }
-    let l = ans.len();
-    for i in 0..l {
-        if ans[i] != out_put[i] {
-            return Ok(false);
+    }
+}
+
+//send a job to the server of this worker, a canceled job is marked so that its programs are killed
+fn report_job(server: &str, worker_id: usize, token: &str, job_res: &Job)-> Result<(), ureq::Error> {
+    let res = with_worker_token(ureq::put(&format!("{}/workers/{}/jobs/{}",server,worker_id,job_res.id)), token)
+        .set("Content-Type", "application/json")
+        .send_string(&serde_json::to_string(job_res).unwrap());
+    if let Err(ureq::Error::Status(400, _)) = res {
+        CANCELED_JOBS.lock().unwrap().insert(job_res.id);
+    }
+    res.map(|_| ())
+}
+
+//judge a claimed job, sending heartbeats in another thread
+fn judge_remote_job(mut job_res: Job, server: &str, worker: &Worker, config: web::Data<Config>) {
+    let job_id = job_res.id;
+    let token = config.server.worker_token.clone().unwrap_or_default();
+    let judging = Arc::new(std::sync::atomic::AtomicBool::new(true));
+    let heartbeat = {
+        let judging = judging.clone();
+        let token = token.clone();
+        let url = format!("{}/workers/{}/heartbeat",server,worker.id);
+        let interval = std::time::Duration::from_millis(worker.lease * 1000 / 3);
+        std::thread::spawn(move || {
+            let mut last = std::time::Instant::now();
+            while judging.load(std::sync::atomic::Ordering::Relaxed) {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+                if last.elapsed() < interval {
+                    continue;
+                }
+                last = std::time::Instant::now();
+                let canceled = with_worker_token(ureq::post(&url), &token).send_string("").ok()
+                    .and_then(|res| res.into_string().ok())
+                    .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok())
+                    .map(|value| value["canceled"] == true).unwrap_or(false);
+                if canceled {
+                    CANCELED_JOBS.lock().unwrap().insert(job_id);
+                }
+            }
+        })
+    };
+    fetch_problem(server, &token, job_res.submission.problem_id);
+    REMOTE_WORKER.with(|remote| *remote.borrow_mut() = Some((server.to_string(), worker.id, token.clone())));
+    execute_job(&mut job_res, config);
+    REMOTE_WORKER.with(|remote| *remote.borrow_mut() = None);
+    //the result is sent until the server answers, a lost job is judged by others anyway
+    for _ in 0..5 {
+        match report_job(server, worker.id, &token, &job_res) {
+            Err(ureq::Error::Transport(e)) => {
+                log::warn!(target: "remote_worker", "Result of job {} can not be sent: {}", job_id, e);
+                std::thread::sleep(std::time::Duration::from_secs(1));
+            },
+            _ => break,
         }
     }
-    Ok(true)
+    judging.store(false, std::sync::atomic::Ordering::Relaxed);
+    let _ = heartbeat.join();
+    CANCELED_JOBS.lock().unwrap().remove(&job_id);
 }
 
-fn cmp_output_strict(caseinfo: Case, caseid: usize)-> Result<bool,io::Error> {
-    let output_reader = read_to_string(format!("tmp/{}.out",caseid))?;
-    let ans_reader = read_to_string(caseinfo.answer_file)?;
-    if ans_reader == output_reader {
-        return Ok(true);
-    } else {return Ok(false);}
+//take the latest version of a problem and its uploaded data files from the server,
+//the known version is used when it fails
+fn fetch_problem(server: &str, token: &str, problem_id: usize) {
+    let fetched = ureq::get(&format!("{}/problems/{}",server,problem_id)).call().ok()
+        .and_then(|res| res.into_string().ok())
+        .and_then(|text| serde_json::from_str::<Problem>(&text).ok());
+    if let Some(problem) = fetched {
+        let mut problems = PROBLEMS_LIST.lock().unwrap();
+        match problems.iter().position(|p| p.id == problem_id) {
+            Some(i) => problems[i] = problem,
+            None => problems.push(problem),
+        }
+    }
+    let files = with_worker_token(ureq::get(&format!("{}/problems/{}/data",server,problem_id)), token).call().ok()
+        .and_then(|res| res.into_string().ok())
+        .and_then(|text| serde_json::from_str::<Vec<serde_json::Value>>(&text).ok())
+        .unwrap_or_default();
+    //kept locked while downloading so that judge workers of this node do not fetch a file twice
+    let mut fetched_data = FETCHED_DATA.lock().unwrap();
+    for file in files {
+        let (name, path) = (file["name"].as_str().unwrap_or(""), file["path"].as_str().unwrap_or(""));
+        let version = (file["size"].as_u64().unwrap_or(0), file["modified"].as_u64().unwrap_or(0));
+        if fetched_data.get(path) == Some(&version) && std::path::Path::new(path).exists() {
+            continue;
+        }
+        let mut data: Vec<u8> = Vec::new();
+        let downloaded = with_worker_token(ureq::get(&format!("{}/problems/{}/data/{}",server,problem_id,name)), token).call()
+            .map_err(|e| e.to_string())
+            .and_then(|res| res.into_reader().read_to_end(&mut data).map_err(|e| e.to_string()));
+        //written to another file first as on the server
+        let tmp_path = format!("{}.download",path);
+        let saved = downloaded.and_then(|_| {
+            std::path::Path::new(path).parent().map(std::fs::create_dir_all).unwrap_or(Ok(()))
+                .and_then(|_| std::fs::write(&tmp_path, &data))
+                .and_then(|_| std::fs::rename(&tmp_path, path))
+                .map_err(|e| e.to_string())
+        });
+        match saved {
+            Ok(()) => { fetched_data.insert(path.to_string(), version); },
+            Err(e) => {
+                let _ = std::fs::remove_file(&tmp_path);
+                log::warn!(target: "remote_worker", "Data file {} of problem {} can not be fetched: {}", name, problem_id, e);
+            },
+        }
+    }
 }
 
-fn special_judge(caseinfo: Case, caseid: usize, spj: Vec<String>)-> Result<(String, String),io::Error> {
-    let mut comargs: Vec<String> = Vec::new();
-    for i in spj.clone() {
-        if i == "%ANSWER%" {
-            comargs.push(caseinfo.answer_file.clone());
-        } else if i == "%OUTPUT%" {
-            comargs.push(format!("tmp/{}.out",caseid));
-        } else {
-            comargs.push(i);
+//register to the server and judge jobs claimed from it, register again when the lease is lost
+fn remote_worker(server: String, name: String, config: web::Data<Config>) {
+    let postworker = PostWorker{ name: name, languages: config.languages.iter().map(|langu| langu.name.clone()).collect() };
+    let token = config.server.worker_token.clone().unwrap_or_default();
+    loop {
+        let registered = with_worker_token(ureq::post(&format!("{}/workers",server)), &token)
+            .set("Content-Type", "application/json")
+            .send_string(&serde_json::to_string(&postworker).unwrap()).ok()
+            .and_then(|res| res.into_string().ok())
+            .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok());
+        let worker = match registered {
+            None => {
+                log::warn!(target: "remote_worker", "Can not register to {}", server);
+                std::thread::sleep(std::time::Duration::from_secs(1));
+                continue;
+            },
+            Some(value) => Worker{ id: value["id"].as_u64().unwrap_or(0) as usize, name: postworker.name.clone(), languages: postworker.languages.clone(),
+                                   job_id: None, lease: value["lease"].as_u64().unwrap_or(30), last_seen: std::time::Instant::now() },
+        };
+        log::info!(target: "remote_worker", "Registered to {} as worker {}", server, worker.id);
+        loop {
+            match with_worker_token(ureq::post(&format!("{}/workers/{}/claim",server,worker.id)), &token).send_string("") {
+                Ok(res) if res.status() == 200 => {
+                    match res.into_string().ok().and_then(|text| serde_json::from_str::<Job>(&text).ok()) {
+                        Some(job_res) => judge_remote_job(job_res, &server, &worker, config.clone()),
+                        None => log::error!(target: "remote_worker", "Invalid job from {}", server),
+                    }
+                },
+                Ok(_) => std::thread::sleep(std::time::Duration::from_millis(500)),
+                Err(ureq::Error::Status(404, _)) => break,
+                Err(e) => {
+                    log::warn!(target: "remote_worker", "Can not claim jobs from {}: {}", server, e);
+                    std::thread::sleep(std::time::Duration::from_secs(1));
+                },
+            }
         }
     }
-    comargs.remove(0);
-    //run spj and get the result
-    let spj_output_u8 = process::Command::new(spj[0].clone())
-                            .args(comargs)
-                            .output()?.stdout;
-    let spj_output = String::from_utf8(spj_output_u8);
-    if spj_output.as_ref().is_err() {
-        return Err(io::Error::new(io::ErrorKind::Other, ""));
-    } else {
-        let _spj_out = spj_output.unwrap();
-        let spj_out: Vec<&str> = _spj_out.split("\n").collect();
-        let b = spj_out[0].to_string();
-        if b != "Accepted" && b != "Wrong Answer" && b != "Time Limit Exceeded" && b != "Memory Limit Exceeded" && b != "Runtime Error" && b != "System Error" {
-            return Err(io::Error::new(io::ErrorKind::Other, ""));
-        } else { return Ok((b,spj_out[1].to_string())); }
-    }   
 }
+//remote worker part end
 
 //server for request post /jobs
 #[post("/jobs")]
@@ -467,145 +2880,31 @@ async fn post_jobs(postjob: web::Json<PostJob>, config: web::Data<Config>) -> im
             else { return actix_web::HttpResponse::BadRequest().json(e.clone()); }
         },
         Ok(saveinfo) => {
-            //create a new job item
+            if let Some(e) = check_problem_type(saveinfo.1.clone()) {
+                return actix_web::HttpResponse::BadRequest().json(e);
+            }
+            //create a new job item and queue it
             let ins_time = Utc::now();
-            let mut job_res = Job{ id:JOB_LIST.lock().unwrap().len(),
+            let mut job_list = JOB_LIST.lock().unwrap();
+            let mut job_res = Job{ id: job_list.len(),
                 created_time:ins_time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
                 updated_time:ins_time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
                 submission:postjob.clone(), 
-                state: String::from("Running"), 
+                state: JobState::Queueing, 
                 result: String::from("Waiting"), 
                 score: 0.0, 
-                cases: Vec::new(), };
-                for i in 0..=saveinfo.1.cases.len() {
-                    job_res.cases.push( CaseResult{ id: i.clone(), result: "Waiting".to_string(), time: 0, memory: 0, info: "".to_string() } );
-                }
-            //build program
-            match build_test(postjob.clone(), saveinfo.0.clone()) {
-                Err(e) => {return actix_web::HttpResponse::BadRequest().json(e);},
-                Ok(status) => if !status.success() {
-                    job_res.state = String::from("Finished");
-                    job_res.result = String::from("Compilation Error");
-                    job_res.cases[0].result = "Compilation Error".to_string();
-                    return actix_web::HttpResponse::Ok().json(job_res);
-                }
-            };
-            job_res.cases[0].result = "Compilation Success".to_string();
-            let mut dy_ratio: f64 = 0.0;
-            if saveinfo.1.ty == "dynamic_ranking" {
-                if saveinfo.1.misc.as_ref().is_none() {
-                    actix_web::HttpResponse::BadRequest().
-                                            json(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "HTTP 400 Bad Request".to_string() });
-
-                } else {
-                    if saveinfo.1.misc.clone().unwrap().dynamic_ranking_ratio.is_none() {
-                        actix_web::HttpResponse::BadRequest().
-                                            json(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "HTTP 400 Bad Request".to_string() });
-
-                    } else {
-                        dy_ratio = saveinfo.1.misc.clone().unwrap().dynamic_ranking_ratio.unwrap();
-                    }
-                }
-            }
-            for i in 0..saveinfo.1.cases.len() {
-                //run program
-                match run_test(saveinfo.1.cases[i].clone(), i).await {
-                    Err(_e) => {
-                        return actix_web::HttpResponse::InternalServerError().
-                            json(MyError{ code: 6, reason: "ERR_INTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() });
-                    },
-                    Ok(state) => {
-                        // check the answer
-                        match state.status {
-                            0 => {
-                                job_res.cases[i+1] = CaseResult{ id: i+1, result: "".to_string(), time: state.runtime, memory: state.memory, info: "".to_string() };
-                                if state.memory > saveinfo.1.cases[i].memory_limit && saveinfo.1.cases[i].memory_limit > 0 {
-                                    job_res.cases[i+1].result = "Memory Limit Exceeded".to_string();
-                                } else {
-                                    //run compare programs
-                                    match &saveinfo.1.clone().ty as &str {
-                                        "standard" | "dynamic_ranking" => match cmp_output_std(saveinfo.1.cases[i].clone(), i) {
-                                            Ok(b) => {
-                                                if b {
-                                                    job_res.cases[i+1].result = "Accepted".to_string();
-                                                    job_res.score += saveinfo.1.cases[i].score * (1.0-dy_ratio);
-                                                } else {
-                                                    job_res.cases[i+1].result = "Wrong Answer".to_string();
-                                                }
-                                            },
-                                            Err(_e) => {
-                                                return actix_web::HttpResponse::InternalServerError().
-                                                    json(MyError{ code: 5, reason: "ERR_EXTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() });
-                                            },},
-                                        "strict" => match cmp_output_strict(saveinfo.1.cases[i].clone(), i) {
-                                            Ok(b) => {
-                                                if b {
-                                                    job_res.cases[i+1].result = "Accepted".to_string();
-                                                    job_res.score += saveinfo.1.cases[i].score;
-                                                } else {
-                                                    job_res.cases[i+1].result = "Wrong Answer".to_string();
-                                                }
-                                            },
-                                            Err(_e) => {
-                                                return actix_web::HttpResponse::InternalServerError().
-                                                    json(MyError{ code: 5, reason: "ERR_EXTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() });
-                                            },},
-                                        "spj" => {
-                                            if saveinfo.1.clone().misc.is_none() {
-                                                return actix_web::HttpResponse::BadRequest()
-                                                    .json(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "HTTP 400 Bad Request".to_string() });
-                                            } else {
-                                                if saveinfo.1.clone().misc.unwrap().special_judge.is_none(){
-                                                    return actix_web::HttpResponse::BadRequest()
-                                                        .json(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "HTTP 400 Bad Request".to_string() });
-                                                } else {
-                                                    match special_judge(saveinfo.1.cases[i].clone(), i, saveinfo.1.clone().misc.unwrap().special_judge.unwrap()) {
-                                                        Err(_e) => {
-                                                            job_res.cases[i+1].result = "SPJ Error".to_string();
-                                                        },
-                                                        Ok(b) => {
-                                                            job_res.cases[i+1].result = b.0.clone();
-                                                            job_res.cases[i+1].info = b.1.clone();
-                                                            if b.0 == "Accepted" {
-                                                                job_res.score += saveinfo.1.cases[i].score;
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        },
-                                        &_ => return actix_web::HttpResponse::BadRequest().
-                                            json(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "HTTP 400 Bad Request".to_string() }),
-                                    
-                                    };
-                                    
-                                }
-                                
-                            },
-                            1 => {
-                                job_res.cases[i+1].result = "Runtime Error".to_string();
-                            },
-                            2 => {
-                                job_res.cases[i+1].result = "Time Limit Exceeded".to_string();
-                            },
-                            _ => return actix_web::HttpResponse::InternalServerError().
-                            json(MyError{ code: 6, reason: "ERR_INTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() }),
-                        }
-                    }
-                }
-            }
-            for i in 1..job_res.cases.len() {
-                if job_res.cases[i].result != "Accepted" {
-                    job_res.result = job_res.cases[i].result.clone();
-                    break;
-                }
+                cases: Vec::new(),
+                subtasks: waiting_subtasks(saveinfo.1.clone()), };
+            for i in 0..=saveinfo.1.cases.len() {
+                job_res.cases.push( CaseResult{ id: i.clone(), result: "Waiting".to_string(), time: 0, wall_time: 0, memory: 0, info: "".to_string(), exit_code: None, signal: None } );
             }
-            if job_res.result == "Waiting" { job_res.result = "Accepted".to_string(); }
-            job_res.state = "Finished".to_string();
             if data_insert("jobs".to_string(), to_string_pretty(&job_res).unwrap()).is_err() {
                 return actix_web::HttpResponse::InternalServerError().json(MyError{ code: 5, reason: "ERR_EXTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() });
             }
-            JOB_LIST.lock().unwrap().push(job_res.clone());
+            publish_job_events(None, &job_res);
+            job_list.push(job_res.clone());
+            drop(job_list);
+            enqueue_job(job_res.id);
             return actix_web::HttpResponse::Ok().json(job_res);
         }
     }
@@ -613,72 +2912,61 @@ async fn post_jobs(postjob: web::Json<PostJob>, config: web::Data<Config>) -> im
 }
 
 //the following 2 are servers for request get /jobs
-#[get("/jobs")]
-async fn get_jobs(job_condi: web::Query<GetJob>)-> impl Responder {
+//find jobs meeting the condition, it is shared by get /jobs and post /rejudges
+fn find_jobs(job_condi: GetJob)-> Result<Vec<Job>, MyError> {
     let mut find_res: Vec<Job>= Vec::new();
+    let mut from = None;
+    let mut to = None;
     if job_condi.from.is_some() {
-        let jf = DateTime::parse_from_str(&job_condi.from.clone().unwrap(),&format!("%Y-%m-%dT%H:%M:%S%.3fZ"));
+        let jf = NaiveDateTime::parse_from_str(&job_condi.from.clone().unwrap(),&format!("%Y-%m-%dT%H:%M:%S%.3fZ"));
         if jf.is_err() {
-            return actix_web::HttpResponse::BadRequest().
-                json(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "Invalid argument from".to_string() });
+            return Err(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "Invalid argument from".to_string() });
         }
+        from = jf.ok();
     }
     if job_condi.to.is_some() {
-        let jt = DateTime::parse_from_str(&job_condi.to.clone().unwrap(),&format!("%Y-%m-%dT%H:%M:%S%.3fZ"));
+        let jt = NaiveDateTime::parse_from_str(&job_condi.to.clone().unwrap(),&format!("%Y-%m-%dT%H:%M:%S%.3fZ"));
         if jt.is_err() {
-            return actix_web::HttpResponse::BadRequest().
-                json(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "Invalid argument from".to_string() });
+            return Err(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "Invalid argument to".to_string() });
         }
+        to = jt.ok();
+    }
+    let mut state = None;
+    if job_condi.state.is_some() {
+        let st = serde_json::from_value::<JobState>(serde_json::Value::String(job_condi.state.clone().unwrap()));
+        if st.is_err() {
+            return Err(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "Invalid argument state".to_string() });
+        }
+        state = st.ok();
+    }
+    //a user name matching no user matches no job
+    let mut name_id: Option<Option<usize>> = None;
+    if job_condi.user_name.is_some() {
+        name_id = Some(USERS_LIST.lock().unwrap().iter().find(|u| u.name == job_condi.user_name.clone().unwrap()).map(|u| u.id));
+    }
+    //every given condition has to hold
+    for i in JOB_LIST.lock().unwrap().iter() {
+        let created = NaiveDateTime::parse_from_str(&i.created_time.clone(),&format!("%Y-%m-%dT%H:%M:%S%.3fZ")).unwrap();
+        if job_condi.user_id.is_some() && i.submission.user_id != job_condi.user_id.unwrap() { continue; }
+        if name_id.is_some() && name_id.unwrap() != Some(i.submission.user_id) { continue; }
+        if job_condi.contest_id.is_some() && i.submission.contest_id != job_condi.contest_id.unwrap() { continue; }
+        if job_condi.problem_id.is_some() && i.submission.problem_id != job_condi.problem_id.unwrap() { continue; }
+        if job_condi.language.is_some() && i.submission.language != job_condi.language.clone().unwrap() { continue; }
+        if from.is_some() && created < from.unwrap() { continue; }
+        if to.is_some() && created > to.unwrap() { continue; }
+        if state.is_some() && i.state != state.unwrap() { continue; }
+        if job_condi.result.is_some() && i.result != job_condi.result.clone().unwrap() { continue; }
+        find_res.push(i.clone());
+    }
+    Ok(find_res)
+}
+
+#[get("/jobs")]
+async fn get_jobs(job_condi: web::Query<GetJob>)-> impl Responder {
+    match find_jobs(job_condi.into_inner()) {
+        Err(e) => actix_web::HttpResponse::BadRequest().json(e),
+        Ok(find_res) => actix_web::HttpResponse::Ok().json(find_res),
     }
-    for i in JOB_LIST.lock().unwrap().clone() {
-        let mut cnt = 0;
-        let mut is_no_argu = true;
-        //check contents requirement
-        if job_condi.user_id.is_some(){
-            if i.submission.user_id == job_condi.user_id.unwrap() {cnt += 1;}
-            is_no_argu = false;
-        } else if job_condi.user_name.is_some() {
-            is_no_argu = false;
-            let mut tid: Option<usize> = None;
-            for i in USERS_LIST.lock().unwrap().clone() {
-                if i.name == job_condi.user_name.clone().unwrap() {
-                    tid = Some(i.id);
-                }
-            }
-            if tid.is_some() {
-                if tid.unwrap() == i.submission.user_id {cnt += 1;}
-            }
-        }  else if job_condi.contest_id.is_some(){
-            if i.submission.contest_id == job_condi.contest_id.unwrap() {cnt += 1;}
-            is_no_argu = false;
-        } else if job_condi.problem_id.is_some() {
-            if i.submission.problem_id == job_condi.problem_id.unwrap() {cnt += 1;}
-            is_no_argu = false;
-        } else if job_condi.language.is_some() {
-            if i.submission.language == job_condi.language.clone().unwrap() {cnt += 1;}
-            is_no_argu = false;
-        } else if job_condi.from.is_some() { 
-            if DateTime::parse_from_str(&i.created_time.clone(),&format!("%Y-%m-%dT%H:%M:%S%.3fZ")).unwrap() 
-                >= DateTime::parse_from_str(&job_condi.from.clone().unwrap(),&format!("%Y-%m-%dT%H:%M:%S%.3fZ")).unwrap() {cnt += 1;}
-            is_no_argu = false;
-        } else if job_condi.to.is_some() { 
-            if DateTime::parse_from_str(&i.created_time.clone(),&format!("%Y-%m-%dT%H:%M:%S%.3fZ")).unwrap() 
-                >= DateTime::parse_from_str(&job_condi.to.clone().unwrap(),&format!("%Y-%m-%dT%H:%M:%S%.3fZ")).unwrap() {cnt += 1;}
-            is_no_argu = false;
-        } else if job_condi.state.is_some() {
-            if job_condi.state.as_ref().unwrap() != "Finished" {
-                return actix_web::HttpResponse::BadRequest().
-                json(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "Invalid argument state".to_string() });
-            }
-            if i.state == job_condi.state.clone().unwrap() {cnt += 1;}
-            is_no_argu = false;
-        } else if job_condi.result.is_some() {
-            if i.result == job_condi.result.clone().unwrap() {cnt += 1;}
-            is_no_argu = false;
-        }
-        if cnt != 0 || is_no_argu { find_res.push(i.clone()); }
-    }
-    return actix_web::HttpResponse::Ok().json(find_res);
 }
 
 #[get("/jobs/{job_id}")]
@@ -707,151 +2995,300 @@ async fn put_jobs(job_id: web::Path<usize>, config: web::Data<Config>)-> impl Re
             .json(MyError{ code: 3, reason: "ERR_NOT_FOUND".to_string(), message: String::from(format!("Job {} not found.",job_id)) });
 
     }
-    //recheck
-    let mut job_res = _job_res.unwrap();
-    job_res.score = 0.0;
-    job_res.state = "Running".to_string();
-    job_res.result = "Waiting".to_string();
-    job_res.cases.clear();
+    let job_res = _job_res.unwrap();
+    if !can_requeue(&job_res) {
+        return actix_web::HttpResponse::BadRequest()
+            .json(MyError{ code: 2, reason: "ERR_INVALID_STATE".to_string(), message: format!("Job {} not finished.",job_id) });
+    }
+    //recheck and queue the job again
     let postjob = job_res.submission.clone();
     let _saveinfo = check_post_job(postjob.clone(), config.clone());
     match _saveinfo {
         Err(e) => { return actix_web::HttpResponse::NotFound().json(e); },
         Ok(saveinfo) => {
-            for i in 0..=saveinfo.1.cases.len() {
-                job_res.cases.push( CaseResult{ id: i.clone(), result: "Waiting".to_string(), time: 0, memory: 0, info: "".to_string() } );
-            }
-            match build_test(postjob.clone(), saveinfo.0.clone()) {
-                Err(e) => {return actix_web::HttpResponse::BadRequest().json(e);},
-                Ok(status) => if !status.success() {
-                    job_res.state = String::from("Finished");
-                    job_res.result = String::from("Compilation Error");
-                    job_res.cases[0].result = "Compilation Error".to_string();
-                    let ins_time = Utc::now();
-                    job_res.updated_time = ins_time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
-                    JOB_LIST.lock().unwrap()[job_res.id] = job_res.clone();
-                    return actix_web::HttpResponse::Ok().json(job_res);
-                }
-            };
-            job_res.cases[0].result = "Compilation Success".to_string();
-            let mut dy_ratio: f64 = 0.0;
-            if saveinfo.1.ty == "dynamic_ranking" {
-                if saveinfo.1.misc.as_ref().is_none() {
-                    actix_web::HttpResponse::BadRequest().
-                                            json(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "HTTP 400 Bad Request".to_string() });
+            if let Some(e) = check_problem_type(saveinfo.1.clone()) {
+                return actix_web::HttpResponse::BadRequest().json(e);
+            }
+            match requeue_job(job_res.id, saveinfo.1, true) {
+                Err(e) if e.code == 2 => return actix_web::HttpResponse::BadRequest().json(e),
+                Err(e) => return actix_web::HttpResponse::InternalServerError().json(e),
+                Ok(job_res) => return actix_web::HttpResponse::Ok().json(job_res),
+            }
+        }
+    }
+}
 
-                } else {
-                    if saveinfo.1.misc.clone().unwrap().dynamic_ranking_ratio.is_none() {
-                        actix_web::HttpResponse::BadRequest().
-                                            json(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "HTTP 400 Bad Request".to_string() });
+//whether a job can be judged again, a canceled job can be judged again only after its worker stops
+fn can_requeue(job_res: &Job)-> bool {
+    job_res.state != JobState::Queueing && job_res.state != JobState::Running && !CANCELED_JOBS.lock().unwrap().contains(&job_res.id)
+}
 
-                    } else {
-                        dy_ratio = saveinfo.1.misc.clone().unwrap().dynamic_ranking_ratio.unwrap();
-                    }
-                }
+//clear results of a job and queue it again, the state is checked and changed under one lock
+//so that a job is never queued twice, a lost job of a worker is queued again even if running
+fn requeue_job(job_id: usize, problem: Problem, only_finished: bool)-> Result<Job, MyError> {
+    let mut job_list = JOB_LIST.lock().unwrap();
+    let mut job_res = job_list[job_id].clone();
+    if only_finished && !can_requeue(&job_res) {
+        return Err(MyError{ code: 2, reason: "ERR_INVALID_STATE".to_string(), message: format!("Job {} not finished.",job_id) });
+    }
+    job_res.score = 0.0;
+    job_res.state = JobState::Queueing;
+    job_res.result = "Waiting".to_string();
+    job_res.cases.clear();
+    job_res.subtasks = waiting_subtasks(problem.clone());
+    for i in 0..=problem.cases.len() {
+        job_res.cases.push( CaseResult{ id: i.clone(), result: "Waiting".to_string(), time: 0, wall_time: 0, memory: 0, info: "".to_string(), exit_code: None, signal: None } );
+    }
+    let ins_time = Utc::now();
+    job_res.updated_time = ins_time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
+    if data_update("jobs".to_string(), job_res.id, to_string_pretty(&job_res).unwrap()).is_err() {
+        return Err(MyError{ code: 5, reason: "ERR_EXTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() });
+    }
+    publish_job_events(Some(&job_list[job_id]), &job_res);
+    job_list[job_id] = job_res.clone();
+    drop(job_list);
+    enqueue_job(job_id);
+    Ok(job_res)
+}
+
+//queue all jobs meeting the condition again, jobs being judged are left out.
+//only administrators can rejudge, as it may queue every job
+#[post("/rejudges")]
+async fn post_rejudges(req: actix_web::HttpRequest, job_condi: web::Json<GetJob>, config: web::Data<Config>)-> impl Responder {
+    if let Some(refused) = check_admin_token(&req, &config) {
+        return refused;
+    }
+    //an empty condition would rejudge every job
+    if job_condi.user_id.is_none() && job_condi.user_name.is_none() && job_condi.contest_id.is_none() && job_condi.problem_id.is_none()
+        && job_condi.language.is_none() && job_condi.from.is_none() && job_condi.to.is_none() && job_condi.state.is_none() && job_condi.result.is_none() {
+        return actix_web::HttpResponse::BadRequest().json(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "Empty rejudge condition".to_string() });
+    }
+    let found = match find_jobs(job_condi.into_inner()) {
+        Err(e) => return actix_web::HttpResponse::BadRequest().json(e),
+        Ok(found) => found,
+    };
+    let mut rejudge_list = REJUDGE_LIST.lock().unwrap();
+    let mut rejudge = Rejudge{ id: rejudge_list.len(), created_time: Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(), jobs: Vec::new() };
+    for job_res in found {
+        let problem = this_problem(job_res.submission.problem_id);
+        if !can_requeue(&job_res) || problem.is_none() || check_problem_type(problem.clone().unwrap()).is_some() {
+            continue;
+        }
+        let before = RejudgeJob{ job_id: job_res.id, result: job_res.result.clone(), score: job_res.score };
+        match requeue_job(job_res.id, problem.unwrap(), true) {
+            //the job has been queued again by someone else
+            Err(e) if e.code == 2 => continue,
+            Err(e) => return actix_web::HttpResponse::InternalServerError().json(e),
+            Ok(_) => {},
+        }
+        rejudge.jobs.push(before);
+    }
+    if data_insert("rejudges".to_string(), to_string_pretty(&rejudge).unwrap()).is_err() {
+        return actix_web::HttpResponse::InternalServerError().json(MyError{ code: 5, reason: "ERR_EXTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() });
+    }
+    rejudge_list.push(rejudge.clone());
+    actix_web::HttpResponse::Ok().json(rejudge_progress(rejudge))
+}
+
+//compare results of jobs in a rejudge with those before it
+fn rejudge_progress(rejudge: Rejudge)-> RejudgeProgress {
+    let job_list = JOB_LIST.lock().unwrap();
+    let mut progress = RejudgeProgress{ id: rejudge.id, created_time: rejudge.created_time, total: rejudge.jobs.len(), judged: 0, changed: 0, jobs: Vec::new() };
+    for before in rejudge.jobs {
+        let job_res = &job_list[before.job_id];
+        let judged = job_res.state != JobState::Queueing && job_res.state != JobState::Running;
+        let changed = judged && (job_res.result != before.result || job_res.score != before.score);
+        if judged { progress.judged += 1; }
+        if changed { progress.changed += 1; }
+        progress.jobs.push(RejudgeDiff{ job_id: before.job_id, state: job_res.state, changed: changed,
+                                        before_result: before.result, before_score: before.score,
+                                        after_result: job_res.result.clone(), after_score: job_res.score });
+    }
+    progress
+}
+
+#[get("/rejudges/{rejudge_id}")]
+async fn get_rejudges_from_id(rejudge_id: web::Path<usize>)-> impl Responder {
+    let rejudge = REJUDGE_LIST.lock().unwrap().get(*rejudge_id).cloned();
+    match rejudge {
+        None => actix_web::HttpResponse::NotFound()
+            .json(MyError{ code: 3, reason: "ERR_NOT_FOUND".to_string(), message: format!("Rejudge {} not found.",rejudge_id) }),
+        Some(rejudge) => actix_web::HttpResponse::Ok().json(rejudge_progress(rejudge)),
+    }
+}
+
+//compile and run a program on custom input, the result is returned directly
+fn test_run(postrun: PostRun, langu: Language, config: web::Data<Config>, workdir: &str)-> Result<RunResult, MyError> {
+    let internal_err = MyError{ code: 6, reason: "ERR_INTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() };
+    let postjob = PostJob{ source_code: postrun.source_code.clone(), language: postrun.language.clone(), user_id: 0, contest_id: 0, problem_id: 0,
+                           outputs: None, files: postrun.files.clone(), archive: postrun.archive.clone(), callback_url: None };
+    let mut res = RunResult{ result: "Compilation Error".to_string(), compile_info: String::new(), stdout: String::new(), stderr: String::new(),
+                             time: 0, wall_time: 0, memory: 0, exit_code: None, signal: None };
+    let (status, compile_info) = cached_build(postjob, langu.clone(), workdir, config.clone())?;
+    res.compile_info = compile_info;
+    if !status.success() {
+        return Ok(res);
+    }
+    let input_file = format!("{}/stdin",workdir);
+    create_work_file(&input_file).and_then(|mut f| f.write_all(postrun.stdin.as_bytes())).map_err(|_| internal_err.clone())?;
+    let time_limit = config.server.run_time_limit.unwrap_or(1_000_000);
+    let memory_limit = config.server.run_memory_limit.unwrap_or(256 << 20);
+    let output_limit = config.server.run_output_limit.unwrap_or(64 << 10);
+    //0 means no limit in judging, so it is taken as the max like a limit above the max
+    let caseinfo = Case{ score: 0.0, input_file: input_file, answer_file: String::new(),
+                         time_limit: postrun.time_limit.filter(|&t| t > 0 && t <= time_limit).unwrap_or(time_limit), wall_time_limit: None,
+                         memory_limit: postrun.memory_limit.filter(|&m| m > 0 && m <= memory_limit).unwrap_or(memory_limit), output_limit: None, output_name: None };
+    let problem = Problem{ id: 0, name: String::new(), ty: "standard".to_string(), misc: None, cases: vec![caseinfo], subtasks: None };
+    let caseinfo = language_limits(problem, langu.clone()).cases[0].clone();
+    let state = run_test(caseinfo.clone(), 0, workdir, langu.sandbox.clone(), output_limit, None).map_err(|_| internal_err.clone())?;
+    res.result = "Finished".to_string();
+    if state.status == 0 && caseinfo.memory_limit > 0 && state.memory > caseinfo.memory_limit {
+        res.result = "Memory Limit Exceeded".to_string();
+    } else if let Some(result) = status_result(state.status) {
+        res.result = result;
+    }
+    let out = read_work_file(&format!("{}/0.out",workdir)).unwrap_or_default();
+    res.stdout = String::from_utf8_lossy(&out[..out.len().min(output_limit as usize)]).to_string();
+    res.stderr = state.stderr;
+    res.time = state.runtime;
+    res.wall_time = state.wall_time;
+    res.memory = state.memory;
+    res.exit_code = state.exit_code;
+    res.signal = state.signal.map(signal_name);
+    Ok(res)
+}
+
+//server for post /runs, test runs are not judged, saved or counted in submission limits
+#[post("/runs")]
+async fn post_runs(postrun: web::Json<PostRun>, config: web::Data<Config>)-> impl Responder {
+    let postrun = postrun.into_inner();
+    let langu = match this_language(config.clone(), postrun.language.clone()) {
+        None => return actix_web::HttpResponse::NotFound()
+            .json(MyError{ code: 3, reason: "ERR_NOT_FOUND".to_string(), message: format!("Language {} Not Found",postrun.language) }),
+        Some(langu) => langu,
+    };
+    let postjob = PostJob{ source_code: String::new(), language: String::new(), user_id: 0, contest_id: 0, problem_id: 0,
+                           outputs: None, files: postrun.files.clone(), archive: postrun.archive.clone(), callback_url: None };
+    if let Some(e) = check_source_files(postjob) {
+        return actix_web::HttpResponse::BadRequest().json(e);
+    }
+    let run_id = {
+        let mut run_count = RUN_COUNT.lock().unwrap();
+        *run_count += 1;
+        *run_count
+    };
+    //compiling and running block, so they are done out of the server thread
+    let res = web::block(move || {
+        let workdir = format!("{}/run_{}", WORK_ROOT.lock().unwrap(), run_id);
+        let res = test_run(postrun, langu, config, &workdir);
+        let _ = std::fs::remove_dir_all(&workdir);
+        res
+    }).await;
+    match res {
+        Ok(Ok(res)) => actix_web::HttpResponse::Ok().json(res),
+        Ok(Err(e)) => actix_web::HttpResponse::InternalServerError().json(e),
+        Err(_e) => actix_web::HttpResponse::InternalServerError()
+            .json(MyError{ code: 6, reason: "ERR_INTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() }),
+    }
+}
+
+//cancel a queued or running job, the programs of a running job are killed by its worker
+#[delete("/jobs/{job_id}")]
+async fn delete_jobs(job_id: web::Path<usize>, config: web::Data<Config>)-> impl Responder {
+    let job_id = job_id.into_inner();
+    let mut job_list = JOB_LIST.lock().unwrap();
+    if job_id >= job_list.len() {
+        return actix_web::HttpResponse::NotFound()
+            .json(MyError{ code: 3, reason: "ERR_NOT_FOUND".to_string(), message: format!("Job {} not found.",job_id) });
+    }
+    match job_list[job_id].state {
+        JobState::Queueing => {
+            JOB_QUEUE.0.lock().unwrap().retain(|&i| i != job_id);
+        },
+        JobState::Running => {
+            CANCELED_JOBS.lock().unwrap().insert(job_id);
+        },
+        _ => return actix_web::HttpResponse::BadRequest()
+            .json(MyError{ code: 2, reason: "ERR_INVALID_STATE".to_string(), message: format!("Job {} can not be canceled.",job_id) }),
+    }
+    let mut job_res = job_list[job_id].clone();
+    job_res.state = JobState::Canceled;
+    job_res.result = "Canceled".to_string();
+    job_res.updated_time = Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
+    publish_job_events(Some(&job_list[job_id]), &job_res);
+    job_list[job_id] = job_res.clone();
+    drop(job_list);
+    if data_update("jobs".to_string(), job_id, to_string_pretty(&job_res).unwrap()).is_err() {
+        return actix_web::HttpResponse::InternalServerError().json(MyError{ code: 5, reason: "ERR_EXTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() });
+    }
+    send_webhooks(job_res.clone(), config);
+    actix_web::HttpResponse::Ok().json(job_res)
+}
+
+//make a server-sent event stream from job events passing the filter, a comment is sent
+//every 15 seconds to keep the connection. with ends_stream the stream ends after an event that is last
+//and passes, as for a stream of one job, otherwise it goes on until the client leaves
+fn event_stream(first: Option<String>, ends_stream: bool, filter: impl Fn(&JobEvent)-> bool + Clone + 'static)-> HttpResponse {
+    let receiver = JOB_EVENTS.subscribe();
+    let stream = futures_util::stream::unfold((receiver, first, false), move |(mut receiver, first, done)| {
+        let pass = filter.clone();
+        async move {
+            if done {
+                return None;
             }
-            for i in 0..saveinfo.1.cases.len() {
-                match run_test(saveinfo.1.cases[i].clone(), i).await {
-                    Err(_e) => {
-                        return actix_web::HttpResponse::InternalServerError().
-                            json(MyError{ code: 6, reason: "ERR_INTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() });
-                    },
-                    Ok(state) => {
-                        match state.status {
-                            0 => {
-                                job_res.cases[i+1] = CaseResult{ id: i+1, result: "".to_string(), time: state.runtime, memory: state.memory, info: "".to_string() };
-                                if state.memory > saveinfo.1.cases[i].memory_limit && saveinfo.1.cases[i].memory_limit > 0 {
-                                    job_res.cases[i+1].result = "Memory Limit Exceeded".to_string();
-                                } else {
-                                    match &saveinfo.1.clone().ty as &str {
-                                        "standard" | "dynamic_ranking" => match cmp_output_std(saveinfo.1.cases[i].clone(), i) {
-                                            Ok(b) => {
-                                                if b {
-                                                    job_res.cases[i+1].result = "Accepted".to_string();
-                                                    job_res.score += saveinfo.1.cases[i].score * (1.0-dy_ratio);
-                                                } else {
-                                                    job_res.cases[i+1].result = "Wrong Answer".to_string();
-                                                }
-                                            },
-                                            Err(_e) => {
-                                                return actix_web::HttpResponse::InternalServerError().
-                                                    json(MyError{ code: 5, reason: "ERR_EXTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() });
-                                            },},
-                                        "strict" => match cmp_output_strict(saveinfo.1.cases[i].clone(), i) {
-                                            Ok(b) => {
-                                                if b {
-                                                    job_res.cases[i+1].result = "Accepted".to_string();
-                                                    job_res.score += saveinfo.1.cases[i].score;
-                                                } else {
-                                                    job_res.cases[i+1].result = "Wrong Answer".to_string();
-                                                }
-                                            },
-                                            Err(_e) => {
-                                                return actix_web::HttpResponse::InternalServerError().
-                                                    json(MyError{ code: 5, reason: "ERR_EXTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() });
-                                            },},
-                                            "spj" => {
-                                                if saveinfo.1.clone().misc.is_none() {
-                                                    return actix_web::HttpResponse::BadRequest()
-                                                        .json(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "HTTP 400 Bad Request".to_string() });
-                                                } else {
-                                                    if saveinfo.1.clone().misc.unwrap().special_judge.is_none(){
-                                                        return actix_web::HttpResponse::BadRequest()
-                                                            .json(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "HTTP 400 Bad Request".to_string() });
-                                                    } else {
-                                                        match special_judge(saveinfo.1.cases[i].clone(), i, saveinfo.1.clone().misc.unwrap().special_judge.unwrap()) {
-                                                            Err(_e) => {
-                                                                job_res.cases[i+1].result = "SPJ Error".to_string();
-                                                            },
-                                                            Ok(b) => {
-                                                                job_res.cases[i+1].result = b.0.clone();
-                                                                job_res.cases[i+1].info = b.1.clone();
-                                                                if b.0 == "Accepted" {
-                                                                    job_res.score += saveinfo.1.cases[i].score;
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                }
-                                            },
-                                        &_ => return actix_web::HttpResponse::BadRequest().
-                                            json(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: "HTTP 400 Bad Request".to_string() }),
-                                    
-                                    };
-                                    
-                                }
-                                
-                            },
-                            1 => {
-                                job_res.cases[i+1].result = "Runtime Error".to_string();
-                            },
-                            2 => {
-                                job_res.cases[i+1].result = "Time Limit Exceeded".to_string();
-                            },
-                            _ => return actix_web::HttpResponse::InternalServerError().
-                            json(MyError{ code: 6, reason: "ERR_INTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() }),
-                        }
-                    }
-                }
+            if let Some(first) = first {
+                return Some((first, (receiver, None, false)));
             }
-            for i in 1..job_res.cases.len() {
-                if job_res.cases[i].result != "Accepted" {
-                    job_res.result = job_res.cases[i].result.clone();
-                    break;
+            loop {
+                match actix_web::rt::time::timeout(std::time::Duration::from_secs(15), receiver.recv()).await {
+                    Err(_) => return Some((": keep-alive\n\n".to_string(), (receiver, None, false))),
+                    Ok(Err(tokio::sync::broadcast::error::RecvError::Closed)) => return None,
+                    //events missed by a slow client are skipped
+                    Ok(Err(tokio::sync::broadcast::error::RecvError::Lagged(_))) => continue,
+                    Ok(Ok(event)) => {
+                        if pass(&event) {
+                            let text = format!("event: {}\ndata: {}\n\n", event.event, event.data);
+                            return Some((text, (receiver, None, ends_stream && event.last)));
+                        }
+                    },
                 }
             }
-            
-            if job_res.result == "Waiting" { job_res.result = "Accepted".to_string(); }
-            job_res.state = "Finished".to_string();
-            let ins_time = Utc::now();
-            job_res.updated_time = ins_time.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
-            if data_update("jobs".to_string(), job_id.clone(), to_string_pretty(&job_res).unwrap()).is_err() {
-                return actix_web::HttpResponse::InternalServerError().json(MyError{ code: 5, reason: "ERR_EXTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() });
-            }
-            JOB_LIST.lock().unwrap()[job_res.id] = job_res.clone();
-            return actix_web::HttpResponse::Ok().json(job_res);
         }
+    });
+    let stream = futures_util::StreamExt::map(stream, |text| Ok::<_, actix_web::Error>(web::Bytes::from(text)));
+    actix_web::HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(stream)
+}
+
+//stream states and case results of a job, it starts with the job as it is now
+#[get("/jobs/{job_id}/events")]
+async fn get_job_events(job_id: web::Path<usize>)-> impl Responder {
+    let job_id = job_id.into_inner();
+    let job_list = JOB_LIST.lock().unwrap();
+    if job_id >= job_list.len() {
+        return actix_web::HttpResponse::NotFound()
+            .json(MyError{ code: 3, reason: "ERR_NOT_FOUND".to_string(), message: format!("Job {} not found.",job_id) });
+    }
+    let first = format!("event: job\ndata: {}\n\n", serde_json::to_string(&job_list[job_id]).unwrap());
+    if job_list[job_id].state != JobState::Queueing && job_list[job_id].state != JobState::Running {
+        //nothing more to send for a judged job
+        return actix_web::HttpResponse::Ok().content_type("text/event-stream").body(first);
+    }
+    //events are sent with JOB_LIST locked, so none is missed between the job and the stream
+    event_stream(Some(first), true, move |event| event.job_id == job_id && event.event != "verdict")
+}
+
+//stream verdicts of jobs in a contest
+#[get("/contests/{contest_id}/events")]
+async fn get_contest_events(contest_id: web::Path<usize>)-> impl Responder {
+    let contest_id = contest_id.into_inner();
+    if contest_id >= CONTESTS_LIST.lock().unwrap().len() {
+        return actix_web::HttpResponse::NotFound()
+            .json(MyError{ code: 3, reason: "ERR_NOT_FOUND".to_string(), message: format!("Contest {} not found.",contest_id) });
     }
+    event_stream(None, false, move |event| event.contest_id == contest_id && event.event == "verdict")
 }
 
 //server for get /users
@@ -911,7 +3348,7 @@ async fn post_users(postuser: web::Json<PostUser>)-> impl Responder {
 }
 
 //check if post contents is legal
-fn check_post_contests(postcon: PostContest, config: web::Data<Config>)-> Option<MyError> {
+fn check_post_contests(postcon: PostContest)-> Option<MyError> {
     //check users
     let mut save_us: Vec<u8> = Vec::new(); 
     save_us.resize(USERS_LIST.lock().unwrap().len(), 0);
@@ -931,11 +3368,7 @@ fn check_post_contests(postcon: PostContest, config: web::Data<Config>)-> Option
         if save_pro.iter().position(|&x| x == i).is_some() {
             return Some( MyError{reason: "ERR_INVALID_ARGUMENT".to_string(), code:1, message: format!("Invalid argument problem_ids")} );
         } else { save_pro.push(i); }
-        let mut m = false;
-        for j in config.problems.clone() {
-            if i == j.id { m = true; break;}
-        }
-        if !m {
+        if this_problem(i).is_none() {
             return Some( MyError{reason: "ERR_NOT_FOUND".to_string(), code:3, message: format!("Problem {} not found.",i)});
         }
     }
@@ -944,9 +3377,9 @@ fn check_post_contests(postcon: PostContest, config: web::Data<Config>)-> Option
 
 //server for post /contents
 #[post("/contests")]
-async fn post_contests(postcon: web::Json<PostContest>, config: web::Data<Config>)-> impl Responder {
+async fn post_contests(postcon: web::Json<PostContest>)-> impl Responder {
     if postcon.id.is_none() {
-        let _che = check_post_contests(postcon.clone(), config.clone());
+        let _che = check_post_contests(postcon.clone());
         if _che.is_some() {
             if _che.as_ref().unwrap().code == 3 {
                 return actix_web::HttpResponse::NotFound().json(_che.unwrap().clone());
@@ -978,7 +3411,7 @@ async fn post_contests(postcon: web::Json<PostContest>, config: web::Data<Config
             return actix_web::HttpResponse::NotFound().json(MyError{reason: "ERR_NOT_FOUND".to_string(), code:3, message: format!("Content {} not found.", postcon.id.unwrap())});
 
         } else {
-            let _che = check_post_contests(postcon.clone(), config.clone());
+            let _che = check_post_contests(postcon.clone());
             if _che.is_some() {
                 if _che.as_ref().unwrap().code == 3 {
                     return actix_web::HttpResponse::NotFound().json(_che.unwrap().clone());
@@ -1033,7 +3466,7 @@ fn find_job_for_rank(user_id: usize, problem_id: usize, contest_id: usize, rule:
     let mut tj: Option<Job> = None;
     for i in vl {
         if i.submission.user_id == user_id && i.submission.problem_id == problem_id
-                && i.submission.contest_id == contest_id {
+                && i.submission.contest_id == contest_id && i.state != JobState::Canceled {
             if tj.as_ref().is_none() { 
                 tj = Some(i.clone());
             }
@@ -1059,7 +3492,8 @@ fn find_casetime_for_dy(problem_id: usize, contest: Contest, casesize: usize)->
     for i in vl {
         if contest.id != 0 && contest.user_ids.clone().iter().find(|&&x| x == i.submission.user_id).is_none() { continue; }
         if i.submission.problem_id == problem_id && i.submission.contest_id == contest.id && i.result == "Accepted" {
-            for j in 1..i.cases.len() {
+            //jobs judged before the problem changed may have more cases
+            for j in 1..i.cases.len().min(casesize + 1) {
                 if casetime[j-1] == 0 {
                     casetime[j-1] = i.cases[j].time.clone();
                 } else {
@@ -1078,7 +3512,8 @@ fn find_job_for_dy(user_id: usize, problem: Problem, contest: Contest, rule: Str
     let vl = JOB_LIST.lock().unwrap().clone();
     let mut tj: Option<Job> = None;
     for i in vl {
-        if i.submission.user_id == user_id && i.submission.problem_id == problem.id && i.submission.contest_id == contest.id {
+        if i.submission.user_id == user_id && i.submission.problem_id == problem.id && i.submission.contest_id == contest.id
+                && i.state != JobState::Canceled {
             if tj.as_ref().is_none() { 
                 tj = Some(i.clone());
             }
@@ -1097,20 +3532,203 @@ fn find_job_for_dy(user_id: usize, problem: Problem, contest: Contest, rule: Str
     }
     //use the job save above and add the dynamic score
     if tj.is_some() && tj.as_ref().unwrap().result == "Accepted" {
-        let ratio = problem.misc.unwrap().dynamic_ranking_ratio.unwrap().clone();
+        let ratio = problem.misc.clone().and_then(|misc| misc.dynamic_ranking_ratio).unwrap_or(0.0);
         let casetime = find_casetime_for_dy(problem.id, contest, problem.cases.len());
         let mut tmp = tj.unwrap().clone();
-        for i in 1..tmp.cases.len() {
-            let sc: f64 = problem.cases[i-1].score * ratio * (casetime[i-1].clone() as f64) / (tmp.cases[i].time.clone() as f64);
-            tmp.score += sc; 
+        //a program too fast to be measured gets the whole bonus
+        let time_ratio = |best: u128, own: u128| if own == 0 { 1.0 } else { best as f64 / own as f64 };
+        if problem.subtasks.is_some() {
+            //scores of cases are not used with subtasks, so the bonus of a subtask goes by the total time of its cases
+            for subtask in problem.subtasks.clone().unwrap() {
+                let mut best: u128 = 0;
+                let mut own: u128 = 0;
+                for &j in subtask.cases.iter().filter(|&&j| j >= 1 && j < tmp.cases.len() && j <= casetime.len()) {
+                    best += casetime[j-1];
+                    own += tmp.cases[j].time;
+                }
+                tmp.score += subtask.score * ratio * time_ratio(best, own);
+            }
+        } else {
+            for i in 1..tmp.cases.len().min(problem.cases.len() + 1) {
+                let sc: f64 = problem.cases[i-1].score * ratio * time_ratio(casetime[i-1], tmp.cases[i].time);
+                tmp.score += sc;
+            }
         }
         tj = Some(tmp);
     }
     tj
 }
 
-fn this_problem(config: web::Data<Config>, id: usize)-> Option<Problem> {
-    for i in config.problems.clone() {
+//problems part
+//problems are saved in PROBLEMS_LIST and table problems in the same order,
+//ids of problems are chosen by config or post /problems and need not be their positions.
+//files of cases are uploaded by put /problems/{id}/data/{name} into {problem_dir}/{id}/,
+//and a case file given without directory in post /problems means such an uploaded file.
+//uploaded files are read back by remote workers only, as they hold answers.
+
+fn problem_dir(config: web::Data<Config>)-> String {
+    config.server.problem_dir.clone().unwrap_or("problems".to_string())
+}
+
+//save a new problem or a problem with an id already used
+fn save_problem(problem: Problem)-> Result<(),rusqlite::Error> {
+    let mut problems = PROBLEMS_LIST.lock().unwrap();
+    match problems.iter().position(|p| p.id == problem.id) {
+        Some(i) => {
+            data_update("problems".to_string(), i, to_string_pretty(&problem).unwrap())?;
+            problems[i] = problem;
+        },
+        None => {
+            data_insert("problems".to_string(), to_string_pretty(&problem).unwrap())?;
+            problems.push(problem);
+        },
+    }
+    Ok(())
+}
+
+//refuse requests of administrators without the admin token, problems run their own commands in judging
+fn check_admin_token(req: &actix_web::HttpRequest, config: &web::Data<Config>)-> Option<HttpResponse> {
+    check_token(req, config.server.admin_token.clone(), "admin")
+}
+
+//server for post /problems, a problem is made without id and updated with id
+#[post("/problems")]
+async fn post_problems(req: actix_web::HttpRequest, postpro: web::Json<PostProblem>, config: web::Data<Config>)-> impl Responder {
+    if let Some(refused) = check_admin_token(&req, &config) {
+        return refused;
+    }
+    let postpro = postpro.into_inner();
+    let id = match postpro.id {
+        Some(id) => {
+            if this_problem(id).is_none() {
+                return actix_web::HttpResponse::NotFound().json(MyError{ code: 3, reason: "ERR_NOT_FOUND".to_string(), message: format!("Problem {} not found.",id) });
+            }
+            id
+        },
+        None => PROBLEMS_LIST.lock().unwrap().iter().map(|p| p.id + 1).max().unwrap_or(0),
+    };
+    let mut problem = Problem{ id: id, name: postpro.name, ty: postpro.ty, misc: postpro.misc, cases: postpro.cases, subtasks: postpro.subtasks };
+    if let Some(e) = check_problem_type(problem.clone()) {
+        return actix_web::HttpResponse::BadRequest().json(e);
+    }
+    for case in problem.cases.iter_mut() {
+        for file in [&mut case.input_file, &mut case.answer_file] {
+            if file.is_empty() || file == "." || file == ".." {
+                return actix_web::HttpResponse::BadRequest()
+                    .json(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: format!("Invalid file name {}",file) });
+            }
+            if !file.contains('/') {
+                *file = format!("{}/{}/{}",problem_dir(config.clone()),id,file);
+            }
+            //files are only read from the problem directory, not from the server or the system
+            let path = std::path::Path::new(file.as_str());
+            if !path.starts_with(problem_dir(config.clone())) || path.components().any(|c| c == std::path::Component::ParentDir) {
+                return actix_web::HttpResponse::BadRequest()
+                    .json(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: format!("Invalid file name {}",file) });
+            }
+        }
+    }
+    if save_problem(problem.clone()).is_err() {
+        return actix_web::HttpResponse::InternalServerError().json(MyError{ code: 5, reason: "ERR_EXTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() });
+    }
+    actix_web::HttpResponse::Ok().json(problem)
+}
+
+//the following 2 are servers for get /problems
+#[get("/problems")]
+async fn get_problems()-> impl Responder {
+    let mut problems = PROBLEMS_LIST.lock().unwrap().clone();
+    problems.sort_by_key(|p| p.id);
+    HttpResponse::Ok().json(problems)
+}
+
+#[get("/problems/{problem_id}")]
+async fn get_problems_from_id(problem_id: web::Path<usize>)-> impl Responder {
+    match this_problem(problem_id.clone()) {
+        Some(problem) => HttpResponse::Ok().json(problem),
+        None => HttpResponse::NotFound().json(MyError{ code: 3, reason: "ERR_NOT_FOUND".to_string(), message: format!("Problem {} not found.", problem_id) }),
+    }
+}
+
+//check the problem and the name of an uploaded file, names starting with . are kept for files being written
+fn check_problem_data(problem_id: usize, file_name: &str)-> Option<HttpResponse> {
+    if this_problem(problem_id).is_none() {
+        return Some(HttpResponse::NotFound().json(MyError{ code: 3, reason: "ERR_NOT_FOUND".to_string(), message: format!("Problem {} not found.", problem_id) }));
+    }
+    if file_name.is_empty() || file_name.contains('/') || file_name.starts_with('.') {
+        return Some(HttpResponse::BadRequest().json(MyError{ code: 1, reason: "ERR_INVALID_ARGUMENT".to_string(), message: format!("Invalid file name {}",file_name) }));
+    }
+    None
+}
+
+//server for get /problems/{id}/data, uploaded files of a problem for remote workers
+#[get("/problems/{problem_id}/data")]
+async fn get_problem_data(req: actix_web::HttpRequest, problem_id: web::Path<usize>, config: web::Data<Config>)-> impl Responder {
+    if let Some(refused) = check_worker_token(&req, &config) {
+        return refused;
+    }
+    let problem_id = problem_id.into_inner();
+    if this_problem(problem_id).is_none() {
+        return HttpResponse::NotFound().json(MyError{ code: 3, reason: "ERR_NOT_FOUND".to_string(), message: format!("Problem {} not found.", problem_id) });
+    }
+    let dir = format!("{}/{}",problem_dir(config.clone()),problem_id);
+    let mut files = Vec::new();
+    //a problem without uploaded files has no directory
+    for entry in std::fs::read_dir(&dir).into_iter().flatten().flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let meta = match entry.metadata() {
+            Ok(meta) if meta.is_file() && !name.starts_with('.') => meta,
+            _ => continue,
+        };
+        let modified = meta.modified().ok().and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok()).map(|time| time.as_millis() as u64).unwrap_or(0);
+        files.push(serde_json::json!({ "name": name, "path": format!("{}/{}",dir,name), "size": meta.len(), "modified": modified }));
+    }
+    HttpResponse::Ok().json(files)
+}
+
+//server for get /problems/{id}/data/{name}, the body is the file
+#[get("/problems/{problem_id}/data/{file_name}")]
+async fn get_problem_data_file(req: actix_web::HttpRequest, path: web::Path<(usize, String)>, config: web::Data<Config>)-> impl Responder {
+    if let Some(refused) = check_worker_token(&req, &config) {
+        return refused;
+    }
+    let (problem_id, file_name) = path.into_inner();
+    if let Some(e) = check_problem_data(problem_id, &file_name) {
+        return e;
+    }
+    match std::fs::read(format!("{}/{}/{}",problem_dir(config.clone()),problem_id,file_name)) {
+        Ok(data) => HttpResponse::Ok().content_type("application/octet-stream").body(data),
+        Err(_) => HttpResponse::NotFound().json(MyError{ code: 3, reason: "ERR_NOT_FOUND".to_string(), message: format!("File {} not found.", file_name) }),
+    }
+}
+
+//server for put /problems/{id}/data/{name}, the body is the file
+#[put("/problems/{problem_id}/data/{file_name}")]
+async fn put_problem_data(req: actix_web::HttpRequest, path: web::Path<(usize, String)>, body: web::Bytes, config: web::Data<Config>)-> impl Responder {
+    if let Some(refused) = check_admin_token(&req, &config) {
+        return refused;
+    }
+    let (problem_id, file_name) = path.into_inner();
+    if let Some(e) = check_problem_data(problem_id, &file_name) {
+        return e;
+    }
+    let dir = format!("{}/{}",problem_dir(config.clone()),problem_id);
+    let path = format!("{}/{}",dir,file_name);
+    //written to another file first so that judging never reads half a file
+    let tmp_path = format!("{}/.{}.upload",dir,file_name);
+    let saved = std::fs::create_dir_all(&dir)
+        .and_then(|_| std::fs::write(&tmp_path, &body))
+        .and_then(|_| std::fs::rename(&tmp_path, &path));
+    if saved.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+        return HttpResponse::InternalServerError().json(MyError{ code: 5, reason: "ERR_EXTERNAL".to_string(), message: "HTTP 500 Internal Server Error".to_string() });
+    }
+    HttpResponse::Ok().json(serde_json::json!({ "problem_id": problem_id, "name": file_name, "path": path, "size": body.len() }))
+}
+//problems part end
+
+fn this_problem(id: usize)-> Option<Problem> {
+    for i in PROBLEMS_LIST.lock().unwrap().clone() {
        if i.id == id {
             return Some(i.clone());
        } 
@@ -1130,7 +3748,7 @@ struct RankTmpSave {
 
 //server for get ///ranklist
 #[get("/contests/{contestId}/ranklist")]
-async fn get_rank(contest_id: web::Path<usize>, rank_arg: web::Query<GetRankArg>, config: web::Data<Config>)-> impl Responder {
+async fn get_rank(contest_id: web::Path<usize>, rank_arg: web::Query<GetRankArg>)-> impl Responder {
     let mut rank_save: Vec<RankTmpSave> = Vec::new();
     let mut rule = String::from("latest");
     if rank_arg.scoring_rule.as_ref().is_some() { 
@@ -1149,7 +3767,7 @@ async fn get_rank(contest_id: web::Path<usize>, rank_arg: web::Query<GetRankArg>
                                                          score: 0.0, jobs: Vec::new() };
             for j in this_contest.problem_ids.clone() {
                 let mut _fj = None;
-                let this_problem = this_problem(config.clone(), j).unwrap();
+                let this_problem = this_problem(j).unwrap();
                 if this_problem.ty == "dynamic_ranking" {
                     _fj = find_job_for_dy(this_contest.user_ids[i].clone(), this_problem.clone(), this_contest.clone(), rule.clone());
                 } else {
@@ -1181,7 +3799,7 @@ async fn get_rank(contest_id: web::Path<usize>, rank_arg: web::Query<GetRankArg>
         }
         
     } else {
-        let mut pros = config.problems.clone();
+        let mut pros = PROBLEMS_LIST.lock().unwrap().clone();
         for i in 0..pros.len() {
             for j in 0..(pros.len()-i-1) {
                 if pros[j+1].id < pros[j].id {
@@ -1364,6 +3982,14 @@ fn create_database()-> Result<(),rusqlite::Error> {
         id   INTEGER PRIMARY KEY,
         contents TEXT NOT NULL
     )", [])?;
+    database.execute("CREATE TABLE rejudges (
+        id   INTEGER PRIMARY KEY,
+        contents TEXT NOT NULL
+    )", [])?;
+    database.execute("CREATE TABLE problems (
+        id   INTEGER PRIMARY KEY,
+        contents TEXT NOT NULL
+    )", [])?;
     database.execute("CREATE TABLE contests (
         id   INTEGER PRIMARY KEY,
         contents TEXT NOT NULL,
@@ -1390,6 +4016,26 @@ fn load_data()-> Result<(),rusqlite::Error> {
     for i in get_jobs_iter {
         JOB_LIST.lock().unwrap().push(serde_json::from_value::<Job>(serde_json::from_str(&i.unwrap().0).unwrap()).unwrap());
     }
+    //databases made before rejudges are added have no such table
+    database.execute("CREATE TABLE IF NOT EXISTS rejudges (
+        id   INTEGER PRIMARY KEY,
+        contents TEXT NOT NULL
+    )", [])?;
+    let mut get_rejudges_from_db = database.prepare("SELECT contents FROM rejudges")?;
+    let get_rejudges_iter = get_rejudges_from_db.query_map([], |row| {Ok(Data(row.get(0)?))})?;
+    for i in get_rejudges_iter {
+        REJUDGE_LIST.lock().unwrap().push(serde_json::from_value::<Rejudge>(serde_json::from_str(&i.unwrap().0).unwrap()).unwrap());
+    }
+    //databases made before problems are added have no such table
+    database.execute("CREATE TABLE IF NOT EXISTS problems (
+        id   INTEGER PRIMARY KEY,
+        contents TEXT NOT NULL
+    )", [])?;
+    let mut get_problems_from_db = database.prepare("SELECT contents FROM problems")?;
+    let get_problems_iter = get_problems_from_db.query_map([], |row| {Ok(Data(row.get(0)?))})?;
+    for i in get_problems_iter {
+        PROBLEMS_LIST.lock().unwrap().push(serde_json::from_value::<Problem>(serde_json::from_str(&i.unwrap().0).unwrap()).unwrap());
+    }
     let mut get_contests_from_db = database.prepare("SELECT contents, subn FROM contests")?;
     let get_contests_iter = get_contests_from_db.query_map([], |row| {Ok((Data(row.get(0)?), Data(row.get(1)?)))})?;
     for _i in get_contests_iter {
@@ -1403,7 +4049,7 @@ fn load_data()-> Result<(),rusqlite::Error> {
 //update contents in line {id} in {table} to {contents}
 fn data_update(table: String, id: usize, contents: String)-> Result<(),rusqlite::Error> {
     let database = Connection::open("data.db")?;
-    database.execute(&format!("UPDATE {} SET contents = '{}' WHERE id = {}",table,contents,id+1), [])?;
+    database.execute(&format!("UPDATE {} SET contents = ? WHERE id = {}",table,id+1), params![&contents])?;
     Ok(())
 }
 
@@ -1436,37 +4082,101 @@ async fn exit() -> impl Responder {
 async fn main() -> std::io::Result<()> {
     //init
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
-    let _ = std::fs::remove_dir_all("tmp");
     *ARGL.lock().unwrap() = Argu::parse();
+    //only the work directories of this process are cleaned
+    if ARGL.lock().unwrap().worker.is_some() {
+        *WORK_ROOT.lock().unwrap() = format!("tmp/worker_{}", std::process::id());
+    }
+    let _ = std::fs::remove_dir_all(&*WORK_ROOT.lock().unwrap());
     let config = serde_json::from_value::<Config>(
         serde_json::from_str(
           &std::fs::read_to_string(ARGL.lock().unwrap().config.clone())?)?)?;
-    
+    //a remote worker only judges, every judge worker of it registers to the server by itself
+    let worker_server = ARGL.lock().unwrap().worker.clone();
+    let worker_name = ARGL.lock().unwrap().worker_name.clone();
+    if let Some(server) = worker_server {
+        *PROBLEMS_LIST.lock().unwrap() = config.problems.clone();
+        let server = server.trim_end_matches('/').to_string();
+        let hostname = std::fs::read_to_string("/etc/hostname").unwrap_or("worker".to_string()).trim().to_string();
+        let name = worker_name.unwrap_or(format!("{}-{}", hostname, std::process::id()));
+        let mut workers = Vec::new();
+        for i in 0..config.server.judge_workers.unwrap_or(1).max(1) {
+            let worker_config = web::Data::new(config.clone());
+            let (server, name) = (server.clone(), format!("{}-{}", name, i));
+            workers.push(std::thread::spawn(move || remote_worker(server, name, worker_config)));
+        }
+        for worker in workers {
+            let _ = worker.join();
+        }
+        return Ok(());
+    }
     if ARGL.lock().unwrap().flush_data {
         let _ = std::fs::remove_file("data.db");
         let _ = create_database();
     }
     let _ = load_data();
+    //problems in config are saved when the database has no problem of their ids,
+    //later changes are made by post /problems
+    for problem in config.problems.clone() {
+        if this_problem(problem.id).is_none() {
+            let _ = save_problem(problem);
+        }
+    }
+    //jobs not finished before last shutdown are judged again
+    for i in JOB_LIST.lock().unwrap().iter_mut() {
+        if i.state == JobState::Queueing || i.state == JobState::Running {
+            i.state = JobState::Queueing;
+            enqueue_job(i.id);
+        }
+    }
     let mut server_address = "127.0.0.1".to_string();
     let mut server_port = 12345;
     if config.server.bind_address.is_some() { server_address = config.server.bind_address.clone().unwrap(); }
     if config.server.bind_port.is_some() { server_port = config.server.bind_port.clone().unwrap(); }
+    //start judge workers, all jobs are left to remote workers when it is 0
+    let mut judge_workers = 1;
+    if config.server.judge_workers.is_some() { judge_workers = config.server.judge_workers.clone().unwrap(); }
+    for _ in 0..judge_workers {
+        let worker_config = web::Data::new(config.clone());
+        std::thread::spawn(move || judge_worker(worker_config));
+    }
+    let reaper_config = web::Data::new(config.clone());
+    std::thread::spawn(move || reap_workers(reaper_config));
     //start server
     HttpServer::new(move || {
         //App::new().app_data(web::Data::new(config.clone()));
         App::new()
             .app_data(web::Data::new(config.clone()))
+            //uploaded problem files can be large
+            .app_data(web::PayloadConfig::new(1 << 30))
             .wrap(Logger::default())
             .service(post_jobs)
             .service(get_jobs)
             .service(put_jobs)
+            .service(delete_jobs)
+            .service(post_rejudges)
+            .service(get_rejudges_from_id)
             .service(get_jobs_from_id)
+            .service(get_job_events)
+            .service(get_contest_events)
+            .service(post_runs)
+            .service(post_workers)
+            .service(get_workers)
+            .service(claim_job)
+            .service(put_worker_job)
+            .service(worker_heartbeat)
             .service(get_users)
             .service(post_users)
             .service(greet)
             .service(post_contests)
             .service(get_contests)
             .service(get_contests_from_id)
+            .service(post_problems)
+            .service(get_problems)
+            .service(get_problems_from_id)
+            .service(put_problem_data)
+            .service(get_problem_data)
+            .service(get_problem_data_file)
             .service(get_rank)
             // DO NOT REMOVE: used in automatic testing
             .service(exit)